@@ -4,8 +4,76 @@ use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::{Data, DeriveInput};
 
+// A fallback variant is a variant holding a single unnamed u16 field, like Unknown(u16).
+// It's used to keep values which are not listed in the enum.
+fn is_fallback_variant(v: &syn::Variant) -> bool {
+    if let syn::Fields::Unnamed(fields) = &v.fields {
+        if fields.unnamed.len() == 1 {
+            if let syn::Type::Path(type_path) = &fields.unnamed[0].ty {
+                return type_path.path.is_ident("u16");
+            }
+        }
+    }
+    false
+}
+
+// Get the fallback variant name if any. At most one fallback variant is allowed.
+fn get_fallback_variant(ast: &DeriveInput) -> Option<String> {
+    if let Data::Enum(enum_token) = &ast.data {
+        let fallbacks: Vec<_> = enum_token
+            .variants
+            .iter()
+            .filter(|v| is_fallback_variant(v))
+            .collect();
+
+        if fallbacks.len() > 1 {
+            panic!("enum {} has more than one fallback variant!", ast.ident);
+        }
+
+        fallbacks.first().map(|v| v.ident.to_string())
+    } else {
+        panic!("<{}> is not an enum!", ast.ident);
+    }
+}
+
+// Get the prefix of the generic text form of values, like TYPE in TYPE65280 (RFC3597), given as
+// #[dns(prefix = "TYPE")]. Only enums with a fallback variant can have one.
+fn get_prefix(ast: &DeriveInput) -> Option<String> {
+    let mut prefix = None;
+
+    for attr in ast.attrs.iter().filter(|attr| attr.path.is_ident("dns")) {
+        let meta = attr
+            .parse_meta()
+            .unwrap_or_else(|e| panic!("invalid dns attribute for enum {}: {}", ast.ident, e));
+
+        if let syn::Meta::List(list) = meta {
+            for nested in list.nested {
+                match nested {
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(nv))
+                        if nv.path.is_ident("prefix") =>
+                    {
+                        if let syn::Lit::Str(s) = nv.lit {
+                            prefix = Some(s.value());
+                            continue;
+                        }
+                        panic!("prefix for enum {} is not a string!", ast.ident);
+                    }
+                    _ => panic!("unknown dns attribute for enum {}!", ast.ident),
+                }
+            }
+        } else {
+            panic!("dns attribute for enum {} is not a list!", ast.ident);
+        }
+    }
+
+    if prefix.is_some() && get_fallback_variant(ast).is_none() {
+        panic!("enum {} has a prefix but no fallback variant!", ast.ident);
+    }
+    prefix
+}
+
 // Verify if the derive macro can applied to an enum which has no
-// non-unit variants
+// non-unit variants, except an optional fallback variant like Unknown(u16)
 //
 // This function panics in this cases:
 //  enum Foo { A(u8), B, C } : all enum variants not unit variants
@@ -14,8 +82,12 @@ use syn::{Data, DeriveInput};
 fn get_enum_data(ast: &DeriveInput) -> Vec<(String, String)> {
     // check first this is an enum
     if let Data::Enum(enum_token) = &ast.data {
-        // get all variants
-        let variants: Vec<_> = enum_token.variants.iter().collect();
+        // get all variants, the fallback one is dealt with separately
+        let variants: Vec<_> = enum_token
+            .variants
+            .iter()
+            .filter(|v| !is_fallback_variant(v))
+            .collect();
 
         // this will hold all variant data
         let mut variant_data = Vec::new();
@@ -69,10 +141,12 @@ fn get_enum_data(ast: &DeriveInput) -> Vec<(String, String)> {
     }
 }
 
-// create code for implementation of standard trait: Default, TryFrom<u8>, FromStr
+// create code for implementation of standard trait: Default, TryFrom<u8>, FromStr, From<enum> for u16
 pub fn dns_enum(ast: &DeriveInput) -> TokenStream {
     // get enum data or panic
     let variant_data = get_enum_data(&ast);
+    let fallback = get_fallback_variant(&ast).map(|f| format_ident!("{}", f));
+    let prefix = get_prefix(&ast);
 
     // grab enum name as an ident and as a string
     let enum_name = &ast.ident;
@@ -101,7 +175,51 @@ pub fn dns_enum(ast: &DeriveInput) -> TokenStream {
         }
     });
 
-    // now create code for implementation of Default, TryFrom<u8>, FromStr
+    let to_u16 = variant_data.iter().map(|v| {
+        // create value and identifier
+        let value = v.1.parse::<u16>().unwrap();
+        let variant = format_ident!("{}", v.0);
+
+        quote! {
+            #enum_name::#variant => #value,
+        }
+    });
+
+    // the generic form is the prefix followed by the decimal value, like TYPE65534 in RFC3597
+    let from_str_fallback = match &prefix {
+        Some(prefix) => quote! {
+            _ => match s.strip_prefix(#prefix) {
+                Some(digits) if digits.bytes().all(|b| b.is_ascii_digit()) => digits
+                    .parse::<u16>()
+                    .map_err(|_| format!("error converting string '{}' to enum type {}", s, #enum_name_s))
+                    .and_then(<#enum_name>::try_from),
+                _ => Err(format!("error converting string '{}' to enum type {}", s, #enum_name_s)),
+            },
+        },
+        None => quote! {
+            _ => Err(format!("error converting string '{}' to enum type {}", s, #enum_name_s)),
+        },
+    };
+
+    // unknown values are either kept in the fallback variant or are an error
+    let (try_from_fallback, to_u16_fallback) = match &fallback {
+        Some(f) => (
+            quote! {
+                _ => Ok(#enum_name::#f(value)),
+            },
+            quote! {
+                #enum_name::#f(value) => value,
+            },
+        ),
+        None => (
+            quote! {
+                _ => Err(format!("error converting u16 value <{}> to enum type {}", value, #enum_name_s)),
+            },
+            quote!(),
+        ),
+    };
+
+    // now create code for implementation of Default, TryFrom<u8>, FromStr, From<enum> for u16
     let impls = quote! {
         // impl Default
         impl Default for #enum_name  {
@@ -126,7 +244,7 @@ pub fn dns_enum(ast: &DeriveInput) -> TokenStream {
             fn try_from(value: u16) -> Result<Self, Self::Error> {
                 match value {
                     #(#try_from_u16)*
-                    _ => Err(format!("error converting u16 value <{}> to enum type {}", value, #enum_name_s)),
+                    #try_from_fallback
                 }
             }
         }
@@ -138,7 +256,17 @@ pub fn dns_enum(ast: &DeriveInput) -> TokenStream {
             fn from_str(s: &str) -> Result<Self, Self::Err> {
                 match s {
                     #(#from_str)*
-                    _ => Err(format!("error converting string '{}' to enum type {}", s, #enum_name_s)),
+                    #from_str_fallback
+                }
+            }
+        }
+
+        // impl From<enum> for u16
+        impl std::convert::From<#enum_name> for u16 {
+            fn from(value: #enum_name) -> Self {
+                match value {
+                    #(#to_u16)*
+                    #to_u16_fallback
                 }
             }
         }
@@ -158,6 +286,10 @@ mod tests {
     const E2: &'static str = "enum Foo { A = 1, B, C, D }";
     const E3: &'static str = "enum Foo { A = 2*3, B = 1 }";
     const E4: &'static str = "enum Foo { A = 1, B = 2, C = 3 }";
+    const E5: &'static str = "enum Foo { A = 1, B = 2, Unknown(u16) }";
+    const E6: &'static str = "enum Foo { A = 1, Other(u16), Unknown(u16) }";
+    const E7: &'static str = r#"#[dns(prefix = "TYPE")] enum Foo { A = 1, Unknown(u16) }"#;
+    const E8: &'static str = r#"#[dns(prefix = "TYPE")] enum Foo { A = 1, B = 2 }"#;
     const S1: &'static str = "struct Point { x : f64 , y : u8 , z : u32 }";
 
     #[test]
@@ -202,4 +334,43 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn fallback_variant() {
+        let input = get_derive_input(E4);
+        assert!(get_fallback_variant(&input).is_none());
+
+        let input = get_derive_input(E5);
+        assert_eq!(get_fallback_variant(&input).unwrap(), "Unknown");
+        assert_eq!(
+            get_enum_data(&input),
+            vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn more_than_one_fallback_variant() {
+        let input = get_derive_input(E6);
+        let _ = get_fallback_variant(&input);
+    }
+
+    #[test]
+    fn prefix() {
+        let input = get_derive_input(E5);
+        assert!(get_prefix(&input).is_none());
+
+        let input = get_derive_input(E7);
+        assert_eq!(get_prefix(&input).unwrap(), "TYPE");
+    }
+
+    #[test]
+    #[should_panic]
+    fn prefix_without_fallback_variant() {
+        let input = get_derive_input(E8);
+        let _ = get_prefix(&input);
+    }
 }
@@ -178,6 +178,15 @@ pub fn dns_to_network(ast: &DeriveInput) -> proc_macro2::TokenStream {
         }
     });
 
+    // same with domain name compression
+    let to_compressed_method_calls = struct_token.fields.iter().map(|f| {
+        let field_name = f.ident.as_ref().unwrap();
+
+        quote! {
+            length += ToNetworkOrder::to_network_bytes_compressed(&self.#field_name, buffer, table)?;
+        }
+    });
+
     // return code to the compiler
     let new_code = quote! {
         #impl_clause {
@@ -186,6 +195,12 @@ pub fn dns_to_network(ast: &DeriveInput) -> proc_macro2::TokenStream {
                 #( #to_method_calls)*
                 Ok(length)
             }
+
+            fn to_network_bytes_compressed(&self, buffer: &mut Vec<u8>, table: &mut crate::network_order::compression::CompressionTable) -> std::io::Result<usize> {
+                let mut length = 0usize;
+                #( #to_compressed_method_calls)*
+                Ok(length)
+            }
         }
     };
 
@@ -39,8 +39,8 @@ pub fn dns_from(input: TokenStream) -> TokenStream {
 }
 
 // Auto-implement the Default, TryFrom<u8>, TryFrom<u16> and FromStr for enums
-// used in DNS lib
-#[proc_macro_derive(DnsEnum)]
+// used in DNS lib. #[dns(prefix = "TYPE")] allows the generic text form TYPE<n> in FromStr.
+#[proc_macro_derive(DnsEnum, attributes(dns))]
 pub fn tls_macro_enum(input: TokenStream) -> TokenStream {
     // Parse the input tokens into a syntax tree
     let ast = parse_macro_input!(input as DeriveInput);
@@ -0,0 +1,477 @@
+//! An asynchronous DNS client on tokio. Concurrent queries share a single UDP socket, each
+//! one being given its own message ID so that responses are dispatched to the right query.
+//!
+//! Cookies and encrypted transports are only supported by the blocking `DNSClient`.
+use std::collections::HashMap;
+use std::io::{self, Cursor};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::debug;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::timeout as within;
+
+use crate::client::{next_timeout, ClientOptions, TransportMode};
+use crate::error::{DNSError, DNSResult};
+use crate::network_order::{FromNetworkOrder, ToNetworkOrder};
+use crate::rfc1035::DNSMessage;
+use crate::transport::{to_socket_addr, DNS_PORT};
+
+// Delay before receiving again after a transient error on the shared socket
+const TRANSIENT_ERROR_DELAY: Duration = Duration::from_millis(10);
+
+// Queries waiting for their response on the shared socket, by message ID
+type Pending = Arc<Mutex<HashMap<u16, (DNSMessage, oneshot::Sender<DNSMessage>)>>>;
+
+#[derive(Debug)]
+pub struct AsyncDNSClient {
+    server: SocketAddr,
+    options: ClientOptions,
+    socket: Arc<UdpSocket>,
+    pending: Pending,
+
+    // task reading the responses from the socket
+    dispatcher: JoinHandle<()>,
+}
+
+impl AsyncDNSClient {
+    // Same server syntax as the blocking client. Only UDP and TCP modes are supported.
+    pub async fn new(server: &str, options: ClientOptions) -> DNSResult<Self> {
+        if !matches!(options.mode, TransportMode::Udp | TransportMode::Tcp) {
+            return Err(DNSError::new(
+                "only UDP and TCP are supported by the async client",
+            ));
+        }
+        let server = to_socket_addr(server, DNS_PORT)?;
+
+        // only the server can send us responses
+        let local: SocketAddr = if server.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(server).await?;
+        let socket = Arc::new(socket);
+
+        let pending = Pending::default();
+        let dispatcher = tokio::spawn(dispatch(socket.clone(), pending.clone()));
+
+        Ok(Self {
+            server,
+            options,
+            socket,
+            pending,
+            dispatcher,
+        })
+    }
+
+    // Send the query and wait for the response. The response is given the ID of the query,
+    // whatever the ID used on the wire.
+    pub async fn query(&self, query: &DNSMessage) -> DNSResult<DNSMessage> {
+        let mut response = self.query_with(self.options.mode, query).await?;
+
+        if self.options.mode == TransportMode::Udp && response.header.flags.truncated {
+            debug!("truncated response, retrying over TCP");
+            response = self.query_with(TransportMode::Tcp, query).await?;
+        }
+
+        response.header.id = query.header.id;
+        Ok(response)
+    }
+
+    // Number of queries waiting for their response
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    // Send the query with the transport, retrying with a longer timeout each time it
+    // timed out
+    async fn query_with(&self, mode: TransportMode, query: &DNSMessage) -> DNSResult<DNSMessage> {
+        let mut timeout = self.options.timeout;
+        let mut attempt = 0;
+
+        loop {
+            let result = match mode {
+                TransportMode::Tcp => self.exchange_tcp(query, timeout).await,
+                _ => self.exchange_udp(query, timeout).await,
+            };
+
+            match result {
+                Err(e) if e.is_timeout() && attempt < self.options.retries => {
+                    attempt += 1;
+                    timeout = next_timeout(timeout, self.options.backoff)?;
+                    debug!(
+                        "timeout, attempt #{} with timeout {:?}",
+                        attempt + 1,
+                        timeout
+                    );
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    // Send the query on the shared socket with a free ID, and wait for the dispatcher to
+    // hand over the response
+    async fn exchange_udp(&self, query: &DNSMessage, timeout: Duration) -> DNSResult<DNSMessage> {
+        let (sender, receiver) = oneshot::channel();
+        let mut message = query.clone();
+        message.header.id = self.register(&message, sender)?;
+        debug!(
+            "query id={} sent as id={}",
+            query.header.id, message.header.id
+        );
+
+        // the query is no longer waited for once answered, timed out, or if this future is
+        // dropped before
+        let _guard = PendingGuard {
+            pending: &self.pending,
+            id: message.header.id,
+        };
+
+        let mut buffer = Vec::new();
+        message.to_network_bytes(&mut buffer)?;
+        self.socket.send(&buffer).await?;
+
+        match within(timeout, receiver).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(DNSError::new("response dispatcher stopped")),
+            Err(_) => Err(timed_out()),
+        }
+    }
+
+    // Pick a random ID not used by another pending query
+    fn register(
+        &self,
+        message: &DNSMessage,
+        sender: oneshot::Sender<DNSMessage>,
+    ) -> DNSResult<u16> {
+        let mut pending = self.pending.lock().unwrap();
+        if self.dispatcher.is_finished() {
+            return Err(DNSError::new("response dispatcher stopped"));
+        }
+        if pending.len() > u16::MAX as usize {
+            return Err(DNSError::new("no message ID left for a new query"));
+        }
+
+        let id = loop {
+            let id = rand::random::<u16>();
+            if !pending.contains_key(&id) {
+                break id;
+            }
+        };
+        let mut message = message.clone();
+        message.header.id = id;
+        pending.insert(id, (message, sender));
+        Ok(id)
+    }
+
+    // A new connection for each query, discarding messages which are not its response
+    async fn exchange_tcp(&self, query: &DNSMessage, timeout: Duration) -> DNSResult<DNSMessage> {
+        within(timeout, async {
+            let mut stream = TcpStream::connect(self.server).await?;
+
+            let mut buffer = Vec::new();
+            query.to_network_bytes(&mut buffer)?;
+            let length = u16::try_from(buffer.len()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "message too long for TCP")
+            })?;
+            stream.write_u16(length).await?;
+            stream.write_all(&buffer).await?;
+
+            loop {
+                let length = stream.read_u16().await?;
+                let mut buffer = vec![0u8; length as usize];
+                stream.read_exact(&mut buffer).await?;
+
+                match parse(&buffer) {
+                    Ok(response) if response.is_response_to(query) => return Ok(response),
+                    Ok(response) => debug!(
+                        "discarding message id={} not matching query id={}",
+                        response.header.id, query.header.id
+                    ),
+                    Err(e) => debug!("discarding malformed message: {:?}", e),
+                }
+            }
+        })
+        .await
+        .map_err(|_| timed_out())?
+    }
+}
+
+// Removes a query from the pending ones when dropped
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    id: u16,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
+
+// The dispatcher is stopped along with the client
+impl Drop for AsyncDNSClient {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+// Read the responses from the shared socket, and give each one to the query it answers.
+// Others (unknown ID, wrong question, malformed) are discarded.
+async fn dispatch(socket: Arc<UdpSocket>, pending: Pending) {
+    let mut buffer = vec![0u8; u16::MAX as usize];
+
+    loop {
+        let received = match socket.recv(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) if is_transient(&e) => {
+                // ICMP errors are reported on the connected socket: wait a bit so that a
+                // burst of them doesn't keep the task spinning
+                debug!("error receiving on the shared socket: {}", e);
+                tokio::time::sleep(TRANSIENT_ERROR_DELAY).await;
+                continue;
+            }
+            Err(e) => {
+                // the socket is unusable: dropping the senders fails the pending queries
+                debug!("stopping the dispatcher: {}", e);
+                pending.lock().unwrap().clear();
+                return;
+            }
+        };
+
+        let response = match parse(&buffer[..received]) {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("discarding malformed message: {:?}", e);
+                continue;
+            }
+        };
+
+        let mut pending = pending.lock().unwrap();
+        let id = response.header.id;
+        match pending.get(&id) {
+            Some((query, _)) if response.is_response_to(query) => {
+                let (_, sender) = pending.remove(&id).unwrap();
+
+                // the query might have timed out in the meantime
+                let _ = sender.send(response);
+            }
+            _ => debug!("discarding message id={} not matching any query", id),
+        }
+    }
+}
+
+// Errors which don't prevent the next messages from being received
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+    )
+}
+
+fn parse(buffer: &[u8]) -> DNSResult<DNSMessage> {
+    let mut message = DNSMessage::default();
+    message.from_network_bytes(&mut Cursor::new(buffer))?;
+    Ok(message)
+}
+
+fn timed_out() -> DNSError {
+    DNSError::Io(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "no matching response received",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket as StdUdpSocket;
+    use std::thread;
+
+    use crate::rfc1035::{DNSQuestion, DNSResourceRecord, PacketType, QClass, QType, RdData};
+
+    // answer with the address 127.0.0.x, x being the number in the queried name: hostx.example.com
+    fn answer(query: &[u8]) -> Vec<u8> {
+        let mut message = parse(query).unwrap();
+        let name = message.question[0].name.clone();
+        let x = name.labels[0]
+            .trim_start_matches("host")
+            .parse::<u32>()
+            .unwrap();
+
+        message.header.flags.packet_type = PacketType::Response;
+        message.push_answer(DNSResourceRecord {
+            name,
+            r#type: QType::A,
+            class: QClass::IN,
+            ttl: 60,
+            rd_length: 4,
+            rd_data: Some(RdData::A(0x7F000000 + x)),
+        });
+
+        let mut buffer = Vec::new();
+        message.to_network_bytes(&mut buffer).unwrap();
+        buffer
+    }
+
+    fn query_for(x: usize) -> DNSMessage {
+        let mut query = DNSMessage::default();
+        query.header.id = 1234;
+        let name = format!("host{}.example.com", x);
+        query.push_question(DNSQuestion::new(&name, QType::A, None).unwrap());
+        query
+    }
+
+    #[tokio::test]
+    async fn concurrent_queries() {
+        // stand-in server: waits for all queries, then answers them in reverse order,
+        // preceded by a stray response
+        const QUERIES: usize = 200;
+        let udp = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let endpoint = udp.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            let mut queries = Vec::new();
+            let mut from = endpoint;
+            while queries.len() < QUERIES {
+                let (received, peer) = udp.recv_from(&mut buffer).unwrap();
+                queries.push(buffer[..received].to_vec());
+                from = peer;
+            }
+
+            let mut stray = answer(&queries[0]);
+            stray[0] ^= 0xFF;
+            udp.send_to(&stray, from).unwrap();
+
+            for query in queries.iter().rev() {
+                udp.send_to(&answer(query), from).unwrap();
+            }
+        });
+
+        let options = ClientOptions {
+            timeout: Duration::from_secs(5),
+            retries: 0,
+            ..ClientOptions::default()
+        };
+        let client = Arc::new(
+            AsyncDNSClient::new(&endpoint.to_string(), options)
+                .await
+                .unwrap(),
+        );
+
+        // all queries have the same ID, and each one gets its own response
+        let tasks: Vec<_> = (0..QUERIES)
+            .map(|x| {
+                let client = client.clone();
+                tokio::spawn(async move { client.query(&query_for(x)).await })
+            })
+            .collect();
+
+        for (x, task) in tasks.into_iter().enumerate() {
+            let response = task.await.unwrap().unwrap();
+            assert_eq!(response.header.id, 1234);
+            assert!(matches!(
+                response.answer[0].rd_data,
+                Some(RdData::A(ip)) if ip == 0x7F000000 + x as u32
+            ));
+        }
+        assert_eq!(client.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn timeout_and_retries() {
+        // stand-in server: ignores the first query, answers the second one
+        let udp = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let endpoint = udp.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            udp.recv_from(&mut buffer).unwrap();
+            let (received, from) = udp.recv_from(&mut buffer).unwrap();
+            udp.send_to(&answer(&buffer[..received]), from).unwrap();
+        });
+
+        // no retry: the first attempt times out
+        let options = ClientOptions {
+            timeout: Duration::from_millis(100),
+            retries: 0,
+            ..ClientOptions::default()
+        };
+        let client = AsyncDNSClient::new(&endpoint.to_string(), options.clone())
+            .await
+            .unwrap();
+        assert!(client.query(&query_for(1)).await.unwrap_err().is_timeout());
+        assert_eq!(client.pending(), 0);
+
+        // the retry is answered
+        let options = ClientOptions {
+            retries: 1,
+            ..options
+        };
+        let client = AsyncDNSClient::new(&endpoint.to_string(), options)
+            .await
+            .unwrap();
+        let response = client.query(&query_for(1)).await.unwrap();
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_server() {
+        // nothing listens on the port: ICMP errors are reported on the shared socket
+        let endpoint = StdUdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let options = ClientOptions {
+            timeout: Duration::from_millis(100),
+            retries: 1,
+            ..ClientOptions::default()
+        };
+        let client = AsyncDNSClient::new(&endpoint.to_string(), options)
+            .await
+            .unwrap();
+
+        // the dispatcher survives them, and queries time out as usual
+        assert!(client.query(&query_for(1)).await.unwrap_err().is_timeout());
+        assert!(!client.dispatcher.is_finished());
+        assert_eq!(client.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_queries() {
+        // stand-in server: never answers
+        let udp = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let endpoint = udp.local_addr().unwrap();
+
+        let options = ClientOptions {
+            timeout: Duration::from_secs(5),
+            retries: 0,
+            ..ClientOptions::default()
+        };
+        let client = AsyncDNSClient::new(&endpoint.to_string(), options)
+            .await
+            .unwrap();
+
+        // the caller gives up before the client does: the IDs are given back
+        for x in 0..10 {
+            let query = query_for(x);
+            assert!(within(Duration::from_millis(20), client.query(&query))
+                .await
+                .is_err());
+        }
+        assert_eq!(client.pending(), 0);
+        drop(udp);
+    }
+}
@@ -0,0 +1,924 @@
+//! A DNS client sending queries to a name server and returning its responses
+use std::io::{self, Cursor};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::debug;
+use rustls::ClientConfig;
+use rustls_pki_types::ServerName;
+use ureq::{Agent, AgentBuilder};
+
+use crate::error::{DNSError, DNSResult};
+use crate::network_order::FromNetworkOrder;
+use crate::rfc1035::{DNSMessage, ResponseCode};
+use crate::rfc6891::{Cookie, EdnsOption, Padding, QUERY_PADDING_BLOCK_LENGTH};
+use crate::transport::{
+    https::{HttpMethod, HttpsTransport},
+    quic::QuicTransport,
+    split_host_port,
+    tcp::TcpTransport,
+    tls::{TlsOptions, TlsTransport},
+    to_socket_addr,
+    udp::UdpTransport,
+    Transport, DNS_OVER_QUIC_PORT, DNS_OVER_TLS_PORT, DNS_PORT,
+};
+
+// How queries are sent to the name server
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TransportMode {
+    #[default]
+    Udp,
+    Tcp,
+    Tls,
+    Https,
+    Quic,
+}
+
+impl TransportMode {
+    // Port used when the server is given without one
+    pub fn default_port(&self) -> u16 {
+        match self {
+            TransportMode::Udp | TransportMode::Tcp => DNS_PORT,
+            TransportMode::Tls => DNS_OVER_TLS_PORT,
+            TransportMode::Https => 443,
+            TransportMode::Quic => DNS_OVER_QUIC_PORT,
+        }
+    }
+
+    // Encrypted transports should pad queries (RFC8467)
+    pub fn is_encrypted(&self) -> bool {
+        matches!(
+            self,
+            TransportMode::Tls | TransportMode::Https | TransportMode::Quic
+        )
+    }
+
+    // DoH queries use ID 0 to be cache friendly (RFC8484 section 4.1), and DoQ
+    // queries must use it (RFC9250 section 4.2.1)
+    pub fn zero_id(&self) -> bool {
+        matches!(self, TransportMode::Https | TransportMode::Quic)
+    }
+}
+
+// How long to wait for a response, and how many times to ask again
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    pub mode: TransportMode,
+
+    // time to wait for the response to the first attempt
+    pub timeout: Duration,
+
+    // number of attempts after the first one timed out
+    pub retries: u8,
+
+    // the timeout is multiplied by this factor on each retry
+    pub backoff: f64,
+
+    // send a DNS cookie in queries having an OPT record (RFC7873)
+    pub cookies: bool,
+
+    // server authentication for DNS over TLS or HTTPS
+    pub tls: TlsOptions,
+
+    // how DNS over HTTPS queries are sent
+    pub method: HttpMethod,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            mode: TransportMode::Udp,
+            timeout: Duration::from_secs(2),
+            retries: 2,
+            backoff: 2.0,
+            cookies: true,
+            tls: TlsOptions::default(),
+            method: HttpMethod::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DNSClient {
+    // not used for DNS over HTTPS, the server being a URL
+    server: Option<SocketAddr>,
+    options: ClientOptions,
+
+    // the client cookie is specific to the server, which returns its own server cookie
+    cookie: Mutex<Cookie>,
+
+    // name checked against the server certificate, and TLS configuration (DoT and DoQ)
+    tls: Option<(ServerName<'static>, Arc<ClientConfig>)>,
+
+    // URL of the DoH server, and the HTTP agent keeping the connections
+    https: Option<(String, Agent)>,
+}
+
+impl DNSClient {
+    // The server is an address or a host name, optionally followed by a port (depending on
+    // the transport mode if not given), e.g.: 1.1.1.1, 1.1.1.1:5353, 2606:4700:4700::1111,
+    // [::1]:5353, dns.google. For DNS over HTTPS, this is the URL of the server, e.g.:
+    // https://cloudflare-dns.com/dns-query
+    pub fn new(server: &str, options: ClientOptions) -> DNSResult<Self> {
+        if options.mode == TransportMode::Https {
+            let agent = AgentBuilder::new()
+                .tls_config(options.tls.client_config()?)
+                .build();
+            return Ok(Self {
+                server: None,
+                options,
+                cookie: Mutex::new(Cookie::new()),
+                tls: None,
+                https: Some((server.to_string(), agent)),
+            });
+        }
+
+        // the certificate is checked against the server host, unless another name is given
+        let tls = if matches!(options.mode, TransportMode::Tls | TransportMode::Quic) {
+            let name = match &options.tls.server_name {
+                Some(name) => name.clone(),
+                None => split_host_port(server)?.0.to_string(),
+            };
+            let name = ServerName::try_from(name)
+                .map_err(|e| DNSError::new(&format!("invalid TLS server name: {}", e)))?;
+            Some((name, options.tls.client_config()?))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            server: Some(to_socket_addr(server, options.mode.default_port())?),
+            options,
+            cookie: Mutex::new(Cookie::new()),
+            tls,
+            https: None,
+        })
+    }
+
+    // Send the query and wait for the response. When the server doesn't accept our cookie,
+    // it returns BADCOOKIE with a fresh server cookie, so the query is sent again once.
+    pub fn query(&self, query: &DNSMessage) -> DNSResult<DNSMessage> {
+        let response = self.query_once(query)?;
+
+        if self.options.cookies && response.response_code() == ResponseCode::BADCOOKIE {
+            debug!("BADCOOKIE, retrying with the new server cookie");
+            self.query_once(query)
+        } else {
+            Ok(response)
+        }
+    }
+
+    // The last cookie sent or received from the server
+    pub fn cookie(&self) -> Cookie {
+        self.cookie.lock().unwrap().clone()
+    }
+
+    // Send the query with our cookie. When a UDP response is truncated, the query is sent
+    // again over TCP (RFC7766)
+    fn query_once(&self, query: &DNSMessage) -> DNSResult<DNSMessage> {
+        let query = self.with_cookie(query);
+        let mut response = self.query_with(self.options.mode, &query)?;
+
+        if self.options.mode == TransportMode::Udp && response.header.flags.truncated {
+            debug!("truncated response, retrying over TCP");
+            response = self.query_with(TransportMode::Tcp, &query)?;
+        }
+
+        self.save_server_cookie(&response);
+        Ok(response)
+    }
+
+    // Add our cookie to a copy of the query, if it has an OPT record. Queries sent over
+    // an encrypted transport are padded as well.
+    fn with_cookie(&self, query: &DNSMessage) -> DNSMessage {
+        let mut query = query.clone();
+
+        if self.options.mode.zero_id() {
+            query.header.id = 0;
+        }
+
+        if let Some(opt) = query.opt_mut() {
+            if self.options.cookies {
+                opt.set_option(EdnsOption::Cookie(self.cookie()));
+            }
+            if self.options.mode.is_encrypted() && opt.padding_mut().is_none() {
+                opt.push_option(EdnsOption::Padding(Padding::block(
+                    QUERY_PADDING_BLOCK_LENGTH,
+                )));
+            }
+        }
+        query
+    }
+
+    // Keep the server cookie to send it back in the next queries. The response has already
+    // been checked to echo our client cookie.
+    fn save_server_cookie(&self, response: &DNSMessage) {
+        if let Some(received) = response.cookie() {
+            if !received.server.is_empty() {
+                self.cookie.lock().unwrap().server = received.server.clone();
+            }
+        }
+    }
+
+    // Send the query with the transport, retrying with a longer timeout each time it
+    // timed out
+    fn query_with(&self, mode: TransportMode, query: &DNSMessage) -> DNSResult<DNSMessage> {
+        let mut timeout = self.options.timeout;
+        let mut attempt = 0;
+
+        loop {
+            let result = self.attempt(mode, timeout, query);
+
+            match result {
+                Err(e) if e.is_timeout() && attempt < self.options.retries => {
+                    attempt += 1;
+                    timeout = next_timeout(timeout, self.options.backoff)?;
+                    debug!(
+                        "timeout, attempt #{} with timeout {:?}",
+                        attempt + 1,
+                        timeout
+                    );
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    // A single query-response exchange, on a new transport
+    fn attempt(
+        &self,
+        mode: TransportMode,
+        timeout: Duration,
+        query: &DNSMessage,
+    ) -> DNSResult<DNSMessage> {
+        match mode {
+            TransportMode::Udp => exchange(
+                &mut UdpTransport::new(self.server()?, timeout)?,
+                query,
+                timeout,
+            ),
+            TransportMode::Tcp => exchange(
+                &mut TcpTransport::connect(self.server()?, timeout)?,
+                query,
+                timeout,
+            ),
+            TransportMode::Tls => {
+                let (name, config) = self
+                    .tls
+                    .clone()
+                    .ok_or_else(|| DNSError::new("TLS is not configured"))?;
+                exchange(
+                    &mut TlsTransport::connect(self.server()?, name, config, timeout)?,
+                    query,
+                    timeout,
+                )
+            }
+            TransportMode::Quic => {
+                let (name, config) = self
+                    .tls
+                    .clone()
+                    .ok_or_else(|| DNSError::new("TLS is not configured"))?;
+                exchange(
+                    &mut QuicTransport::connect(self.server()?, &name.to_str(), config, timeout)?,
+                    query,
+                    timeout,
+                )
+            }
+            TransportMode::Https => {
+                let (url, agent) = self
+                    .https
+                    .clone()
+                    .ok_or_else(|| DNSError::new("HTTPS is not configured"))?;
+                exchange(
+                    &mut HttpsTransport::new(agent, &url, self.options.method, timeout),
+                    query,
+                    timeout,
+                )
+            }
+        }
+    }
+
+    // Address of the server, for all transports except HTTPS
+    fn server(&self) -> DNSResult<SocketAddr> {
+        self.server
+            .ok_or_else(|| DNSError::new("no server address"))
+    }
+}
+
+// The timeout of the next attempt, multiplied by the backoff factor. An error if the factor
+// is negative, not a number, or makes the timeout overflow.
+pub(crate) fn next_timeout(timeout: Duration, backoff: f64) -> DNSResult<Duration> {
+    Duration::try_from_secs_f64(timeout.as_secs_f64() * backoff).map_err(|e| {
+        DNSError::new(&format!(
+            "invalid timeout {:?} with backoff {}: {}",
+            timeout, backoff, e
+        ))
+    })
+}
+
+// Send a query and receive its response on the same transport. Messages which are not
+// the response to the query (wrong id or question, malformed) are discarded, and we keep
+// on waiting until the timeout expires.
+fn exchange<T: Transport + ?Sized>(
+    transport: &mut T,
+    query: &DNSMessage,
+    timeout: Duration,
+) -> DNSResult<DNSMessage> {
+    query.send(transport)?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(DNSError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no matching response received",
+            )));
+        }
+        transport.set_timeout(remaining)?;
+
+        // only transport errors end the exchange, any message received can be decoded
+        let buffer = transport.recv()?;
+        let mut response = DNSMessage::default();
+        match response.from_network_bytes(&mut Cursor::new(buffer.as_slice())) {
+            Ok(()) if response.is_response_to(query) => return Ok(response),
+            Ok(()) => debug!(
+                "discarding message id={} not matching query id={}",
+                response.header.id, query.header.id
+            ),
+            Err(e) => debug!("discarding malformed message: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Cursor, Read, Write};
+    use std::net::{TcpListener, TcpStream, UdpSocket};
+    use std::sync::mpsc;
+    use std::thread;
+
+    use base64::{
+        engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+        Engine,
+    };
+    use quinn::crypto::rustls::QuicServerConfig;
+    use rustls::crypto::ring::default_provider;
+    use rustls::{ServerConfig, ServerConnection, StreamOwned};
+    use rustls_pki_types::PrivatePkcs8KeyDer;
+
+    use crate::network_order::{FromNetworkOrder, ToNetworkOrder};
+    use crate::rfc1035::{
+        DNSQuestion, DNSResourceRecord, DomainName, PacketType, QClass, QType, RdData,
+    };
+    use crate::rfc6891::{OptionCode, OPT};
+    use crate::transport::https::DNS_MESSAGE;
+    use crate::transport::quic::DOQ_ALPN;
+    use crate::transport::tcp::{read_framed, write_framed};
+    use crate::transport::tls::spki_pin;
+
+    // build the bytes of a response to the query bytes, with or without an answer
+    fn response_to(query: &[u8], truncated: bool) -> Vec<u8> {
+        let mut message = DNSMessage::default();
+        message.from_network_bytes(&mut Cursor::new(query)).unwrap();
+
+        message.header.flags.packet_type = PacketType::Response;
+        message.header.flags.truncated = truncated;
+        if !truncated {
+            message.push_answer(DNSResourceRecord {
+                name: DomainName::try_from("www.example.com").unwrap(),
+                r#type: QType::A,
+                class: QClass::IN,
+                ttl: 60,
+                rd_length: 4,
+                rd_data: Some(RdData::A(0x7F000001)),
+            });
+        }
+
+        let mut buffer = Vec::new();
+        message.to_network_bytes(&mut buffer).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn truncated_fallback_to_tcp() {
+        // stand-in server: UDP always truncates, TCP answers
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let endpoint = udp.local_addr().unwrap();
+        let tcp = TcpListener::bind(endpoint).unwrap();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            let (received, from) = udp.recv_from(&mut buffer).unwrap();
+            udp.send_to(&response_to(&buffer[..received], true), from)
+                .unwrap();
+        });
+        thread::spawn(move || {
+            let (mut stream, _) = tcp.accept().unwrap();
+            let query = read_framed(&mut stream).unwrap();
+            write_framed(&mut stream, &response_to(&query, false)).unwrap();
+        });
+
+        let mut query = DNSMessage::default();
+        query.push_question(DNSQuestion::new("www.example.com", QType::A, None).unwrap());
+
+        let client = DNSClient::new(&endpoint.to_string(), ClientOptions::default()).unwrap();
+        let response = client.query(&query).unwrap();
+        assert!(!response.header.flags.truncated);
+        assert_eq!(response.header.id, query.header.id);
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    #[test]
+    fn timeout_and_retries() {
+        // stand-in server: ignores the first query, answers the second one
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let endpoint = udp.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            udp.recv_from(&mut buffer).unwrap();
+            let (received, from) = udp.recv_from(&mut buffer).unwrap();
+            udp.send_to(&response_to(&buffer[..received], false), from)
+                .unwrap();
+        });
+
+        let mut query = DNSMessage::default();
+        query.push_question(DNSQuestion::new("www.example.com", QType::A, None).unwrap());
+
+        // no retry: the first attempt times out
+        let options = ClientOptions {
+            timeout: Duration::from_millis(100),
+            retries: 0,
+            ..ClientOptions::default()
+        };
+        let client = DNSClient::new(&endpoint.to_string(), options.clone()).unwrap();
+        assert!(client.query(&query).unwrap_err().is_timeout());
+
+        // the retry is answered
+        let options = ClientOptions {
+            retries: 1,
+            ..options
+        };
+        let client = DNSClient::new(&endpoint.to_string(), options).unwrap();
+        let response = client.query(&query).unwrap();
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    #[test]
+    fn backoff() {
+        let timeout = Duration::from_millis(100);
+        assert_eq!(
+            next_timeout(timeout, 2.0).unwrap(),
+            Duration::from_millis(200)
+        );
+        for backoff in [-1.0, f64::NAN, f64::INFINITY, 1e300] {
+            assert!(next_timeout(timeout, backoff).is_err());
+        }
+    }
+
+    #[test]
+    fn discard_unmatched_responses() {
+        // stand-in server: first sends stray messages, then the real response
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let endpoint = udp.local_addr().unwrap();
+        let stranger = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            let (received, from) = udp.recv_from(&mut buffer).unwrap();
+            let query = &buffer[..received];
+
+            // right response but from another address
+            stranger.send_to(&response_to(query, false), from).unwrap();
+
+            // wrong id
+            let mut stray = response_to(query, false);
+            stray[0] ^= 0xFF;
+            udp.send_to(&stray, from).unwrap();
+
+            // wrong question
+            let mut other = DNSMessage::default();
+            other.header.id = u16::from_be_bytes([query[0], query[1]]);
+            other.push_question(DNSQuestion::new("www.example.org", QType::A, None).unwrap());
+            let mut stray = Vec::new();
+            other.to_network_bytes(&mut stray).unwrap();
+            udp.send_to(&response_to(&stray, false), from).unwrap();
+
+            // garbage, and a message cut short in the header
+            udp.send_to(&[0xFF; 5], from).unwrap();
+            udp.send_to(&[0x12, 0x34, 0x81, 0x80, 0x00], from).unwrap();
+
+            // the question is echoed with another case
+            let mut response = response_to(query, false);
+            response[13] = b'W';
+            udp.send_to(&response, from).unwrap();
+        });
+
+        let mut query = DNSMessage::default();
+        query.push_question(DNSQuestion::new("www.example.com", QType::A, None).unwrap());
+
+        let options = ClientOptions {
+            timeout: Duration::from_secs(1),
+            retries: 0,
+            ..ClientOptions::default()
+        };
+        let client = DNSClient::new(&endpoint.to_string(), options).unwrap();
+        let response = client.query(&query).unwrap();
+        assert_eq!(response.header.id, query.header.id);
+        assert_eq!(response.question[0].name.labels[0], "Www");
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    #[test]
+    fn cookies() {
+        // stand-in server: requires a server cookie, issued with BADCOOKIE
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let endpoint = udp.local_addr().unwrap();
+        const SERVER_COOKIE: [u8; 8] = [0x5E; 8];
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            loop {
+                let (received, from) = udp.recv_from(&mut buffer).unwrap();
+                let mut query = DNSMessage::default();
+                query
+                    .from_network_bytes(&mut Cursor::new(&buffer[..received]))
+                    .unwrap();
+                let mut cookie = query.cookie().unwrap().clone();
+                let accepted = cookie.server == SERVER_COOKIE;
+
+                // a spoofed response with a wrong client cookie comes first
+                let mut response = response_to(&buffer[..received], false);
+                let mut spoofed = DNSMessage::default();
+                spoofed
+                    .from_network_bytes(&mut Cursor::new(response.as_slice()))
+                    .unwrap();
+                spoofed
+                    .opt_mut()
+                    .unwrap()
+                    .set_option(EdnsOption::Cookie(Cookie::new()));
+                let mut bytes = Vec::new();
+                spoofed.to_network_bytes(&mut bytes).unwrap();
+                udp.send_to(&bytes, from).unwrap();
+
+                // then the real one
+                let mut message = DNSMessage::default();
+                message
+                    .from_network_bytes(&mut Cursor::new(response.as_slice()))
+                    .unwrap();
+                cookie.server = SERVER_COOKIE.to_vec();
+                let opt = message.opt_mut().unwrap();
+                opt.set_option(EdnsOption::Cookie(cookie));
+                if !accepted {
+                    message.set_response_code(ResponseCode::BADCOOKIE);
+                    message.answer.clear();
+                    message.header.an_count = 0;
+                }
+                response.clear();
+                message.to_network_bytes(&mut response).unwrap();
+                udp.send_to(&response, from).unwrap();
+            }
+        });
+
+        let mut query = DNSMessage::default();
+        query.push_question(DNSQuestion::new("www.example.com", QType::A, None).unwrap());
+        query.push_additional(DNSResourceRecord::from(OPT::default()));
+
+        let options = ClientOptions {
+            timeout: Duration::from_secs(1),
+            retries: 0,
+            ..ClientOptions::default()
+        };
+        let client = DNSClient::new(&endpoint.to_string(), options).unwrap();
+        let client_cookie = client.cookie().client;
+
+        // BADCOOKIE first, then the retry is answered
+        let response = client.query(&query).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answer.len(), 1);
+        assert_eq!(response.cookie().unwrap().client, client_cookie);
+        assert_eq!(client.cookie().server, SERVER_COOKIE);
+
+        // the server cookie is now sent right away
+        let response = client.query(&query).unwrap();
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    // TLS configuration of stand-in servers, with a self-signed certificate for localhost
+    fn tls_server_config() -> (rcgen::CertifiedKey, ServerConfig) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+        let config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certified.cert.der().clone()], key.into())
+            .unwrap();
+        (certified, config)
+    }
+
+    #[test]
+    fn dns_over_tls() {
+        let (certified, config) = tls_server_config();
+        let cert = certified.cert.der().clone();
+        let config = Arc::new(config);
+
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = tcp.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for stream in tcp.incoming() {
+                let conn = ServerConnection::new(config.clone()).unwrap();
+                let mut tls = StreamOwned::new(conn, stream.unwrap());
+
+                // the handshake fails when the client rejects the certificate
+                let query = match read_framed(&mut tls) {
+                    Ok(query) => query,
+                    Err(_) => continue,
+                };
+
+                // only answer padded queries
+                if query
+                    .len()
+                    .is_multiple_of(QUERY_PADDING_BLOCK_LENGTH as usize)
+                {
+                    write_framed(&mut tls, &response_to(&query, false)).unwrap();
+                }
+            }
+        });
+
+        let mut query = DNSMessage::default();
+        query.push_question(DNSQuestion::new("www.example.com", QType::A, None).unwrap());
+        query.push_additional(DNSResourceRecord::from(OPT::default()));
+
+        let ca_file = std::env::temp_dir().join(format!("dnslib-dot-{}.pem", std::process::id()));
+        std::fs::write(&ca_file, certified.cert.pem()).unwrap();
+        let pin = spki_pin(&cert).unwrap();
+
+        let query_with = |tls: TlsOptions| {
+            let options = ClientOptions {
+                mode: TransportMode::Tls,
+                timeout: Duration::from_secs(1),
+                retries: 0,
+                tls,
+                ..ClientOptions::default()
+            };
+            DNSClient::new(&endpoint.to_string(), options)?.query(&query)
+        };
+
+        // the certificate is checked against the CA file, for the given name
+        let with_ca = TlsOptions {
+            server_name: Some("localhost".to_string()),
+            ca_file: Some(ca_file.clone()),
+            ..TlsOptions::default()
+        };
+        let response = query_with(with_ca.clone()).unwrap();
+        assert_eq!(response.answer.len(), 1);
+
+        // the name doesn't match the certificate
+        let wrong_name = TlsOptions {
+            server_name: Some("example.com".to_string()),
+            ..with_ca.clone()
+        };
+        assert!(query_with(wrong_name).is_err());
+
+        // unknown CA
+        let no_ca = TlsOptions {
+            server_name: Some("localhost".to_string()),
+            ..TlsOptions::default()
+        };
+        assert!(query_with(no_ca).is_err());
+
+        // the pin alone authenticates the server
+        let pinned = TlsOptions {
+            spki_pins: vec![pin.clone()],
+            ..TlsOptions::default()
+        };
+        let response = query_with(pinned).unwrap();
+        assert_eq!(response.answer.len(), 1);
+
+        // both the CA and the pin are checked
+        let wrong_pin = TlsOptions {
+            spki_pins: vec![STANDARD.encode([0u8; 32])],
+            ..with_ca
+        };
+        assert!(query_with(wrong_pin).is_err());
+
+        std::fs::remove_file(&ca_file).unwrap();
+    }
+
+    // a minimal HTTP/1.1 DoH server: the query is either the body of a POST request or the
+    // dns parameter of a GET request
+    fn serve_https(stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut stream = stream;
+
+        loop {
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).unwrap() == 0 {
+                return;
+            }
+            let target = request_line.split(' ').nth(1).unwrap().to_string();
+
+            let mut length = 0;
+            let mut content_type = String::new();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header == "\r\n" {
+                    break;
+                }
+                let (name, value) = header.trim_end().split_once(": ").unwrap();
+                match name.to_lowercase().as_str() {
+                    "content-length" => length = value.parse().unwrap(),
+                    "content-type" => content_type = value.to_string(),
+                    _ => (),
+                }
+            }
+
+            let query = if request_line.starts_with("POST") {
+                assert_eq!(content_type, DNS_MESSAGE);
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                body
+            } else {
+                let dns = target.split_once("?dns=").unwrap().1;
+                URL_SAFE_NO_PAD.decode(dns).unwrap()
+            };
+
+            // the wrong path answers with a web page, others with a wrong ID or an oversized body
+            let (media_type, body) = if target.starts_with("/dns-query") {
+                (DNS_MESSAGE, response_to(&query, false))
+            } else if target.starts_with("/mismatch") {
+                let mut body = response_to(&query, false);
+                body[0] ^= 0xFF;
+                (DNS_MESSAGE, body)
+            } else if target.starts_with("/large") {
+                (DNS_MESSAGE, vec![0u8; u16::MAX as usize + 1])
+            } else {
+                ("text/html", b"<html></html>".to_vec())
+            };
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+                media_type,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    }
+
+    #[test]
+    fn dns_over_https() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = tcp.local_addr().unwrap();
+
+        thread::spawn(move || {
+            for stream in tcp.incoming() {
+                let stream = stream.unwrap();
+                thread::spawn(move || serve_https(stream));
+            }
+        });
+
+        let mut query = DNSMessage::default();
+        query.push_question(DNSQuestion::new("www.example.com", QType::A, None).unwrap());
+        query.push_additional(DNSResourceRecord::from(OPT::default()));
+
+        let query_with = |path: &str, method: HttpMethod| {
+            let options = ClientOptions {
+                mode: TransportMode::Https,
+                timeout: Duration::from_secs(1),
+                retries: 0,
+                method,
+                ..ClientOptions::default()
+            };
+            let url = format!("http://{}{}", endpoint, path);
+            DNSClient::new(&url, options)?.query(&query)
+        };
+
+        // the query ID is 0 for both methods
+        for method in [HttpMethod::Post, HttpMethod::Get] {
+            let response = query_with("/dns-query", method).unwrap();
+            assert_eq!(response.header.id, 0);
+            assert_eq!(response.answer.len(), 1);
+            assert!(response
+                .opt()
+                .unwrap()
+                .option(OptionCode::PADDING)
+                .is_some());
+        }
+
+        // not a DNS message
+        assert!(query_with("/", HttpMethod::Post).is_err());
+
+        // a mismatched response ends the exchange at once, without retrying
+        let err = query_with("/mismatch", HttpMethod::Post).unwrap_err();
+        assert!(!err.is_timeout());
+
+        // a body too large for a DNS message isn't truncated
+        let err = query_with("/large", HttpMethod::Post).unwrap_err();
+        assert!(matches!(err, DNSError::DNS(e) if e.contains("larger than 65535")));
+    }
+
+    #[test]
+    fn dns_over_quic() {
+        // stand-in server answering each query on its stream
+        let (certified, mut config) = tls_server_config();
+        config.alpn_protocols = vec![DOQ_ALPN.to_vec()];
+        let config = quinn::ServerConfig::with_crypto(Arc::new(
+            QuicServerConfig::try_from(Arc::new(config)).unwrap(),
+        ));
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async {
+                let server =
+                    quinn::Endpoint::server(config, "127.0.0.1:0".parse().unwrap()).unwrap();
+                sender.send(server.local_addr().unwrap()).unwrap();
+
+                while let Some(incoming) = server.accept().await {
+                    let Ok(connection) = incoming.await else {
+                        continue;
+                    };
+                    while let Ok((mut send, mut recv)) = connection.accept_bi().await {
+                        let mut length = [0u8; 2];
+                        recv.read_exact(&mut length).await.unwrap();
+                        let mut query = vec![0u8; u16::from_be_bytes(length) as usize];
+                        recv.read_exact(&mut query).await.unwrap();
+
+                        // only answer padded queries with ID 0
+                        if query[..2] != [0, 0]
+                            || !query
+                                .len()
+                                .is_multiple_of(QUERY_PADDING_BLOCK_LENGTH as usize)
+                        {
+                            continue;
+                        }
+                        // a wrong ID for mismatch.example.com
+                        let mut response = response_to(&query, false);
+                        if query.windows(8).any(|label| label == b"mismatch") {
+                            response[0] ^= 0xFF;
+                        }
+                        send.write_all(&(response.len() as u16).to_be_bytes())
+                            .await
+                            .unwrap();
+                        send.write_all(&response).await.unwrap();
+                        send.finish().unwrap();
+                    }
+                }
+            });
+        });
+        let endpoint = receiver.recv().unwrap();
+
+        let query_for = |name: &str| {
+            let mut query = DNSMessage::default();
+            query.push_question(DNSQuestion::new(name, QType::A, None).unwrap());
+            query.push_additional(DNSResourceRecord::from(OPT::default()));
+            query
+        };
+        let query = query_for("www.example.com");
+
+        let client_with = |tls: TlsOptions| {
+            let options = ClientOptions {
+                mode: TransportMode::Quic,
+                timeout: Duration::from_secs(1),
+                retries: 0,
+                tls,
+                ..ClientOptions::default()
+            };
+            DNSClient::new(&endpoint.to_string(), options)
+        };
+        let query_with = |tls: TlsOptions| client_with(tls)?.query(&query);
+
+        // authenticated with the pin
+        let pinned = TlsOptions {
+            spki_pins: vec![spki_pin(certified.cert.der()).unwrap()],
+            ..TlsOptions::default()
+        };
+        let response = query_with(pinned.clone()).unwrap();
+        assert_eq!(response.header.id, 0);
+        assert_eq!(response.answer.len(), 1);
+
+        // a mismatched response ends the exchange at once, without retrying
+        let err = client_with(pinned)
+            .unwrap()
+            .query(&query_for("mismatch.example.com"))
+            .unwrap_err();
+        assert!(!err.is_timeout());
+
+        // unknown CA
+        let no_ca = TlsOptions {
+            server_name: Some("localhost".to_string()),
+            ..TlsOptions::default()
+        };
+        assert!(query_with(no_ca).is_err());
+    }
+}
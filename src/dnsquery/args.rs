@@ -1,11 +1,21 @@
 //! Manage command line arguments here.
 use std::fs::OpenOptions;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use clap::{Arg, Command};
 use simplelog::*;
 
-use dnslib::{error::DNSResult, rfc1035::QType};
+use dnslib::{
+    client::{ClientOptions, TransportMode},
+    error::{DNSError, DNSResult},
+    resolv_conf::{ResolvConf, RESOLV_CONF},
+    rfc1035::QType,
+    rfc6891::ClientSubnet,
+    transport::https::HttpMethod,
+};
 
 /// This structure holds the command line arguments.
 #[derive(Debug, Default)]
@@ -14,6 +24,11 @@ pub struct CliOptions {
     pub ns: String,
     pub domain: String,
     pub no_opt: bool,
+    pub subnet: Option<ClientSubnet>,
+    pub nsid: bool,
+    pub trace: bool,
+    pub search: Option<ResolvConf>,
+    pub client: ClientOptions,
     pub debug: bool,
 }
 
@@ -41,8 +56,9 @@ impl CliOptions {
                 Arg::new("ns")
                     .short('n')
                     .long("ns")
-                    .required(true)
-                    .long_help("Name server to address")
+                    .required(false)
+                    .conflicts_with("https")
+                    .long_help("Name server to address, optionally with a port: 1.1.1.1, 1.1.1.1:5353, 2606:4700:4700::1111, [::1]:5353. Defaults to the first one of /etc/resolv.conf")
                     .value_name("NAMESERVER")
                     .takes_value(true),
             )
@@ -71,15 +87,168 @@ impl CliOptions {
                     .long_help("Use OPT record")
                     .takes_value(false),
             )
+            .arg(
+                Arg::new("subnet")
+                    .long("subnet")
+                    .required(false)
+                    .long_help("Client subnet sent in the EDNS Client Subnet option, e.g.: 192.0.2.0/24")
+                    .value_name("SUBNET")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("nsid")
+                    .long("nsid")
+                    .required(false)
+                    .long_help("Ask the server for its identifier (NSID)")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::new("trace")
+                    .long("trace")
+                    .required(false)
+                    .conflicts_with_all(&["ns", "https", "tls", "quic"])
+                    .long_help("Resolve iteratively from the root servers, showing each delegation")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::new("search")
+                    .long("search")
+                    .required(false)
+                    .conflicts_with_all(&["trace", "no-opt", "subnet", "nsid"])
+                    .long_help("Complete the domain with the search list of /etc/resolv.conf, according to its ndots option. /etc/hosts is looked up first")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::new("no-cookie")
+                    .long("no-cookie")
+                    .required(false)
+                    .long_help("Don't send a DNS cookie")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::new("tcp")
+                    .short('t')
+                    .long("tcp")
+                    .required(false)
+                    .long_help("Use TCP instead of UDP")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::new("tls")
+                    .long("tls")
+                    .required(false)
+                    .conflicts_with("tcp")
+                    .long_help("Use DNS over TLS (port 853 by default)")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::new("quic")
+                    .long("quic")
+                    .required(false)
+                    .conflicts_with_all(&["tcp", "tls"])
+                    .long_help("Use DNS over QUIC (port 853 by default)")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::new("https")
+                    .long("https")
+                    .required(false)
+                    .conflicts_with_all(&["tcp", "tls", "quic"])
+                    .long_help("Use DNS over HTTPS with the server URL, e.g.: https://cloudflare-dns.com/dns-query")
+                    .value_name("URL")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("get")
+                    .long("get")
+                    .required(false)
+                    .long_help("Send DNS over HTTPS queries with GET instead of POST")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::new("tls-host")
+                    .long("tls-host")
+                    .required(false)
+                    .long_help("Name to check the server certificate against, if not the name server host (DoT and DoQ)")
+                    .value_name("HOST")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("tls-ca")
+                    .long("tls-ca")
+                    .required(false)
+                    .long_help("PEM file of CA certificates to verify the server against, instead of the built-in roots")
+                    .value_name("FILE")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("tls-pin")
+                    .long("tls-pin")
+                    .required(false)
+                    .multiple_occurrences(true)
+                    .long_help("Base64 SHA-256 of the server certificate public key (SPKI). Can be repeated")
+                    .value_name("PIN")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("timeout")
+                    .long("timeout")
+                    .required(false)
+                    .long_help("Time to wait for a response, in milliseconds")
+                    .value_name("MILLISECONDS")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("retries")
+                    .long("retries")
+                    .required(false)
+                    .long_help("Number of times the query is sent again when no response is received")
+                    .value_name("RETRIES")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("backoff")
+                    .long("backoff")
+                    .required(false)
+                    .long_help("Factor by which the timeout is multiplied on each retry, at least 1")
+                    .value_name("FACTOR")
+                    .takes_value(true),
+            )
             .get_matches();
 
-        // save all cli options into a structure
-        let mut options = CliOptions::default();
-
-        options.ns = String::from(matches.value_of("ns").unwrap());
+        // save all cli options into a structure. Domain is required
+        let mut options = CliOptions {
+            domain: String::from(matches.value_of("domain").unwrap()),
+            ..Default::default()
+        };
 
-        // domain is required
-        options.domain = String::from(matches.value_of("domain").unwrap());
+        // without a name server (or DoH URL), the system configuration is used like dig does
+        // no name server either when tracing, the root servers being asked first
+        options.trace = matches.is_present("trace");
+        if matches.is_present("search") {
+            options.search = Some(ResolvConf::system()?);
+        }
+        match matches.value_of("ns").or(matches.value_of("https")) {
+            Some(ns) => options.ns = String::from(ns),
+            None if options.trace => (),
+            None => {
+                let conf = match options.search.clone() {
+                    Some(conf) => conf,
+                    None => ResolvConf::system()?,
+                };
+                let ns = conf.nameservers.first().ok_or_else(|| {
+                    DNSError::new(&format!("no nameserver found in {}", RESOLV_CONF))
+                })?;
+                // the port is the one of the transport, a link-local address keeps its scope
+                options.ns = match ns {
+                    SocketAddr::V6(addr) if addr.scope_id() != 0 => {
+                        format!("[{}%{}]", addr.ip(), addr.scope_id())
+                    }
+                    _ => ns.ip().to_string(),
+                };
+                options.client = conf.client_options();
+            }
+        }
 
         // if QType is not present, defaults to A
         if matches.is_present("qtype") {
@@ -90,6 +259,90 @@ impl CliOptions {
 
         options.no_opt = matches.is_present("no-opt");
 
+        // ECS is an EDNS option
+        if let Some(subnet) = matches.value_of("subnet") {
+            if options.no_opt {
+                return Err(DNSError::new("--subnet needs the OPT record"));
+            }
+            options.subnet = Some(ClientSubnet::from_str(subnet)?);
+        }
+
+        options.nsid = matches.is_present("nsid");
+        if options.nsid && options.no_opt {
+            return Err(DNSError::new("--nsid needs the OPT record"));
+        }
+        options.client.cookies = !matches.is_present("no-cookie");
+        if matches.is_present("tcp") {
+            options.client.mode = TransportMode::Tcp;
+        }
+
+        // DoT server authentication
+        if matches.is_present("tls") {
+            options.client.mode = TransportMode::Tls;
+        }
+        if matches.is_present("quic") {
+            options.client.mode = TransportMode::Quic;
+        }
+        if matches.is_present("https") {
+            options.client.mode = TransportMode::Https;
+        }
+        if matches.is_present("get") {
+            if options.client.mode != TransportMode::Https {
+                return Err(DNSError::new("--get needs --https"));
+            }
+            options.client.method = HttpMethod::Get;
+        }
+        if !matches!(
+            options.client.mode,
+            TransportMode::Tls | TransportMode::Quic
+        ) && matches.is_present("tls-host")
+        {
+            return Err(DNSError::new("--tls-host needs --tls or --quic"));
+        }
+        if !options.client.mode.is_encrypted()
+            && ["tls-ca", "tls-pin"]
+                .iter()
+                .any(|arg| matches.is_present(arg))
+        {
+            return Err(DNSError::new(
+                "--tls-ca and --tls-pin need --tls, --quic or --https",
+            ));
+        }
+        options.client.tls.server_name = matches.value_of("tls-host").map(String::from);
+        options.client.tls.ca_file = matches.value_of("tls-ca").map(PathBuf::from);
+        if let Some(pins) = matches.values_of("tls-pin") {
+            options.client.tls.spki_pins = pins.map(String::from).collect();
+        }
+
+        // timeout, retries and backoff override the client defaults or the system ones
+        if let Some(timeout) = matches.value_of("timeout") {
+            let ms = timeout
+                .parse::<u64>()
+                .map_err(|e| DNSError::new(&format!("invalid timeout '{}': {}", timeout, e)))?;
+            if ms == 0 {
+                return Err(DNSError::new("timeout must be at least 1 millisecond"));
+            }
+            options.client.timeout = Duration::from_millis(ms);
+        }
+        if let Some(retries) = matches.value_of("retries") {
+            options.client.retries = retries
+                .parse::<u8>()
+                .map_err(|e| DNSError::new(&format!("invalid retries '{}': {}", retries, e)))?;
+        }
+        // the timeout can't shrink from one attempt to the next
+        if let Some(backoff) = matches.value_of("backoff") {
+            let factor = backoff
+                .parse::<f64>()
+                .map_err(|e| DNSError::new(&format!("invalid backoff '{}': {}", backoff, e)))?;
+            if !factor.is_finite() || factor < 1.0 {
+                return Err(DNSError::new(&format!(
+                    "invalid backoff '{}': must be a number not below 1",
+                    backoff
+                )));
+            }
+            options.client.backoff = factor;
+        }
+
         // set debug for logging
         options.debug = matches.is_present("debug");
 
@@ -1,17 +1,18 @@
 //! Display method: as we can't impl the Display trait outside the module where it's defined, and
 //! to not put these methods in the lib, use a wrapper
 use std::fmt;
-use std::io::Cursor;
 
 use log::debug;
 
 use dnslib::{
     error::DNSResult,
-    network_order::FromNetworkOrder,
+    iterative::Step,
+    resolver::Answer,
     rfc1035::{
         DNSPacketFlags, DNSPacketHeader, DNSQuery, DNSQuestion, DNSResourceRecord, DNSResponse,
-        DomainName, PacketType, QType, RdData, A, AAAA, HINFO, MX, NS, SOA, TXT,
+        DomainName, PacketType, QClass, QType, RdData, MX, SOA, TXT,
     },
+    rfc6891::{EdnsOption, ExtendedError, OptionCode, OPT},
 };
 
 // a helper macro for displaying RR data when it's easy
@@ -59,6 +60,147 @@ impl fmt::Display for DisplayWrapper<'_, MX> {
     }
 }
 
+impl fmt::Display for DisplayWrapper<'_, OPT> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "OPT payload:{} version:{} do:{}",
+            self.0.udp_payload_size,
+            self.0.ttl.version,
+            self.0.ttl.dnssec_ok()
+        )?;
+
+        for option in &self.0.options {
+            write!(f, " {}", DisplayWrapper(option))?;
+        }
+        Ok(())
+    }
+}
+
+// e.g.: 6 (DNSSEC Bogus) "signature expired"
+impl fmt::Display for DisplayWrapper<'_, ExtendedError> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", u16::from(self.0.info_code), self.0.info_code)?;
+
+        if !self.0.extra_text.is_empty() {
+            write!(f, " \"{}\"", self.0.extra_text)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DisplayWrapper<'_, OptionCode> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            OptionCode::Unknown(code) => write!(f, "OPT{}", code),
+            _ => write!(f, "{:?}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for DisplayWrapper<'_, EdnsOption> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", DisplayWrapper(&self.0.code()))?;
+
+        match self.0 {
+            // same as dig: address/source/scope
+            EdnsOption::ClientSubnet(ecs) => match ecs.ip() {
+                Ok(ip) => write!(
+                    f,
+                    "{}/{}/{}",
+                    ip, ecs.source_prefix_length, ecs.scope_prefix_length
+                )?,
+                Err(_) => write!(f, "{:?}", ecs)?,
+            },
+            // client cookie, followed by the server cookie if any
+            EdnsOption::Cookie(cookie) => {
+                for b in cookie.client.iter().chain(&cookie.server) {
+                    write!(f, "{:02X}", b)?;
+                }
+            }
+            EdnsOption::ExtendedError(ede) => write!(f, "{}", DisplayWrapper(ede))?,
+            // hex and printable characters, as dig does
+            EdnsOption::Nsid(nsid) => {
+                for b in nsid {
+                    write!(f, "{:02X}", b)?;
+                }
+                if !nsid.is_empty() {
+                    let text: String = nsid
+                        .iter()
+                        .map(|b| {
+                            if b.is_ascii_graphic() {
+                                *b as char
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect();
+                    write!(f, " (\"{}\")", text)?;
+                }
+            }
+            EdnsOption::Padding(padding) => write!(f, "{}", padding.length)?,
+            EdnsOption::TcpKeepalive(timeout) => match timeout {
+                Some(timeout) => write!(f, "{:.1}s", *timeout as f32 / 10.0)?,
+                None => write!(f, "-")?,
+            },
+            EdnsOption::Unknown { bytes, .. } => {
+                for b in bytes {
+                    write!(f, "{:02X}", b)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DisplayWrapper<'_, TXT> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // each character-string is quoted, as dig does
+        for (i, cs) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "\"{}\"", cs)?;
+        }
+        Ok(())
+    }
+}
+
+// RFC3597 generic presentation for unknown types: TYPE<n>
+impl fmt::Display for DisplayWrapper<'_, QType> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            QType::Unknown(type_code) => write!(f, "TYPE{}", type_code),
+            _ => write!(f, "{:?}", self.0),
+        }
+    }
+}
+
+// RFC3597 generic presentation for unknown classes: CLASS<n>
+impl fmt::Display for DisplayWrapper<'_, QClass> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            QClass::Unknown(class) => write!(f, "CLASS{}", class),
+            _ => write!(f, "{:?}", self.0),
+        }
+    }
+}
+
+// RFC3597 generic presentation for unknown RDATA: \# <length> <hex>
+impl fmt::Display for DisplayWrapper<'_, Vec<u8>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\\# {}", self.0.len())?;
+
+        if !self.0.is_empty() {
+            write!(f, " ")?;
+            for b in self.0 {
+                write!(f, "{:02X}", b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Display for DisplayWrapper<'_, DNSPacketHeader> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // output depends on whether it's a query or a response
@@ -104,8 +246,10 @@ impl fmt::Display for DisplayWrapper<'_, DNSQuestion> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "domain:{} qtype:{:?} class:{:?}",
-            self.0.name, self.0.r#type, self.0.class
+            "domain:{} qtype:{} class:{}",
+            self.0.name,
+            DisplayWrapper(&self.0.r#type),
+            DisplayWrapper(&self.0.class)
         )
     }
 }
@@ -138,48 +282,66 @@ pub fn display_data(dns_response: &DNSResponse) -> DNSResult<()> {
         display_resource(answer);
     }
 
-    if dns_response.authority.is_some() {
-        for authorative in dns_response.authority.as_ref().unwrap() {
-            display_resource(authorative);
-        }
+    for authorative in &dns_response.authority {
+        display_resource(authorative);
+    }
+
+    for add in &dns_response.additional {
+        display_resource(add);
     }
 
     Ok(())
 }
 
+// One step of an iterative resolution, as dig +trace does: the records received, then
+// where they come from
+pub fn display_step(step: &Step) {
+    let response = &step.response;
+    for rr in response.answer.iter().chain(&response.authority) {
+        display_record(rr);
+    }
+    println!(
+        ";; from {}({}) for zone {}\n",
+        step.server.name, step.server.address, step.zone
+    );
+}
+
+// The aliases followed from the name asked for, then the records found at the end of the chain
+pub fn display_lookup(answer: &Answer) {
+    println!(";; following {} alias(es)", answer.chain.len());
+    for rr in answer.chain.iter().chain(&answer.records) {
+        display_record(rr);
+    }
+}
+
+// A record on a single line, in zone file order
+fn display_record(rr: &DNSResourceRecord) {
+    print!(
+        "{} {} {} {} ",
+        rr.name,
+        rr.ttl,
+        DisplayWrapper(&rr.class),
+        DisplayWrapper(&rr.r#type)
+    );
+    display_resource(rr);
+}
+
 pub fn display_resource(rr: &DNSResourceRecord) {
-    match rr.r#type {
-        QType::A => match &rr.rd_data {
-            Some(RdData::A(ipv4)) => {
-                println!("{}", std::net::Ipv4Addr::from(*ipv4));
-            }
-            _ => panic!("oups"),
-        },
-        QType::HINFO => match &rr.rd_data {
-            Some(RdData::HINFO(hinfo)) => {
-                println!("HINFO: {:?}", hinfo);
-            }
-            _ => panic!("oups"),
-        },
-        QType::AAAA => match &rr.rd_data {
-            Some(RdData::AAAA(ipv6)) => {
-                println!("{}", std::net::Ipv6Addr::from(*ipv6));
-            }
-            _ => panic!("oups"),
-        },
-        // QType::SOA => {
-        //     let mut soa = SOA::default();
-        //     soa.from_network_bytes(cursor)?;
-        //     println!("{}", DisplayWrapper(&soa));
-        // }
-        // QType::TXT => {
-        //     let mut txt = TXT::default();
-        //     txt.from_network_bytes(cursor)?;
-        //     println!("\"{}\"", txt);
-        // }
-        // QType::NS => rr_display!(NS, cursor),
-        // QType::MX => rr_display!(MX, cursor),
-        _ => unimplemented!(),
+    // even a known type might come with opaque RDATA, which is shown in the RFC3597 form
+    match &rr.rd_data {
+        Some(RdData::A(ipv4)) => println!("{}", std::net::Ipv4Addr::from(*ipv4)),
+        Some(RdData::HINFO(hinfo)) => println!("HINFO: {:?}", hinfo),
+        Some(RdData::AAAA(ipv6)) => println!("{}", std::net::Ipv6Addr::from(*ipv6)),
+        Some(RdData::CNAME(cname)) => println!("{}", DisplayWrapper(cname)),
+        Some(RdData::DNAME(dname)) => println!("{}", DisplayWrapper(dname)),
+        Some(RdData::NS(ns)) => println!("{}", DisplayWrapper(ns)),
+        Some(RdData::PTR(ptr)) => println!("{}", DisplayWrapper(ptr)),
+        Some(RdData::MX(mx)) => println!("{}", DisplayWrapper(mx)),
+        Some(RdData::SOA(soa)) => println!("{}", DisplayWrapper(soa)),
+        Some(RdData::TXT(txt)) => println!("{}", DisplayWrapper(txt)),
+        Some(RdData::OPT(opt)) => println!("{}", DisplayWrapper(opt)),
+        Some(RdData::Unknown { bytes, .. }) => println!("{}", DisplayWrapper(bytes)),
+        None => println!("{}", DisplayWrapper(&Vec::<u8>::new())),
     }
 }
 
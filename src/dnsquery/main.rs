@@ -1,18 +1,15 @@
 //! A DNS resource query
-use std::io::Cursor;
-use std::net::UdpSocket;
-
 use log::debug;
 
 // our DNS library
 use dnslib::{
+    client::DNSClient,
     error::DNSResult,
-    format_buffer,
-    network_order::FromNetworkOrder,
-    rfc1035::{
-        DNSPacketHeader, DNSQuery, DNSQuestion, DNSResponse, ResponseCode, MAX_DNS_PACKET_SIZE, OPT,
-    },
-    util::pretty_cursor,
+    hosts::Hosts,
+    iterative::IterativeResolver,
+    resolver::Resolver,
+    rfc1035::{DNSQuery, DNSQuestion, DNSResourceRecord, DNSResponse, QClass, QType, ResponseCode},
+    rfc6891::{EdnsOption, OPT},
 };
 
 // mod dnsrequest;
@@ -22,16 +19,38 @@ mod args;
 use args::CliOptions;
 
 mod display;
-use display::{display_data, DisplayWrapper};
+use display::{display_data, display_lookup, display_step, DisplayWrapper};
 
 fn main() -> DNSResult<()> {
     // manage arguments from command line
     let options = CliOptions::options()?;
     debug!("options: {:?}", &options);
 
-    // bind to an ephemeral local port
-    let socket = UdpSocket::bind("0.0.0.0:0")?;
-    debug!("socket: {:?}", &socket);
+    // follow the delegations from the root servers, like dig +trace
+    if options.trace {
+        let resolver = IterativeResolver::new(options.client);
+        let steps = resolver.resolve(&options.domain, options.qtype)?;
+        for step in &steps {
+            display_step(step);
+        }
+        return display_answer(&steps.last().unwrap().response);
+    }
+
+    // try the names built from the search list, as the system resolver does, /etc/hosts first
+    if let Some(conf) = options.search {
+        let client = DNSClient::new(&options.ns, options.client)?;
+        let mut resolver = Resolver::with_search(client, conf.search, conf.ndots);
+        match Hosts::system() {
+            Ok(hosts) => resolver = resolver.with_hosts(hosts),
+            Err(e) => debug!("hosts table not used: {:?}", e),
+        }
+        let answer = resolver.search(&options.domain, options.qtype, QClass::IN)?;
+        display_answer(&answer.response)?;
+        if !answer.chain.is_empty() {
+            display_lookup(&answer);
+        }
+        return Ok(());
+    }
 
     // create the query from command line arguments
     let mut query = DNSQuery::default();
@@ -42,48 +61,63 @@ fn main() -> DNSResult<()> {
     // by default we want OPT
     if !options.no_opt {
         // add the OPT pseudo-RR to the additional data
-        let opt = OPT::default();
-        query.additional = Some(vec![Box::new(opt)]);
+        let mut opt = OPT::default();
+        if let Some(subnet) = &options.subnet {
+            opt.push_option(EdnsOption::ClientSubnet(subnet.clone()));
+        }
+        if options.nsid {
+            opt.push_option(EdnsOption::Nsid(Vec::new()));
+        }
+        query.push_additional(DNSResourceRecord::from(opt));
     }
     debug!("query: {:?}", &query);
     println!("QUERY: {}", DisplayWrapper(&query));
 
-    // send query
-    query.send(&socket, &options.ns)?;
-
-    // receive request
-    let _received = receive_answer(&socket, options.debug)?;
+    // UDP by default, falling back to TCP on truncation, or one of the encrypted transports
+    let client = DNSClient::new(&options.ns, options.client)?;
+
+    // send query and receive response
+    let response = client.query(&query)?;
+    display_answer(&response)?;
+
+    // the name is an alias whose target records were not given along
+    let aliased = response
+        .answer
+        .iter()
+        .any(|rr| matches!(rr.r#type, QType::CNAME | QType::DNAME));
+    let answered = response.answer.iter().any(|rr| rr.r#type == options.qtype);
+    if aliased && !answered && !matches!(options.qtype, QType::CNAME | QType::DNAME) {
+        let resolver = Resolver::new(client);
+        let answer = resolver.lookup(&options.domain, options.qtype, QClass::IN)?;
+        display_lookup(&answer);
+    }
 
     Ok(())
 }
 
-fn receive_answer(socket: &UdpSocket, debug: bool) -> DNSResult<usize> {
-    // receive packet from endpoint
-    let mut buf = [0; MAX_DNS_PACKET_SIZE];
-    let received = socket.recv(&mut buf)?;
-    let slice = &buf[..received];
-    debug!("received buffer: {}", format_buffer!("X", &slice));
-    debug!("received buffer: [{}", format_buffer!("C", &slice));
-
-    // cursor is necessary to use the ToFromNetworkOrder trait
-    let mut cursor = Cursor::new(&buf[..received]);
-
-    // get response
-    let mut dns_response = DNSResponse::default();
-    debug!("==================> before dns_response.from_network_bytes()");
-    dns_response.from_network_bytes(&mut cursor)?;
-    debug!("==================> after dns_response.from_network_bytes()");
+fn display_answer(dns_response: &DNSResponse) -> DNSResult<()> {
+    // the RCODE, along with the reasons the server might give (EDE)
+    let rcode = dns_response.response_code();
+    let mut explained = format!("{:?}", rcode);
+    for ede in dns_response.extended_errors() {
+        explained.push_str(&format!(" EDE:{}", DisplayWrapper(ede)));
+    }
 
     // check return code
-    if dns_response.header.flags.response_code != ResponseCode::NoError {
-        eprintln!("Response error!");
+    if rcode != ResponseCode::NoError {
+        eprintln!("Response error: {}", explained);
         std::process::exit(1);
     }
 
+    // an answer might come with an EDE too, like a stale answer
+    if !dns_response.extended_errors().is_empty() {
+        println!("rcode: {}", explained);
+    }
+
     // display data to user
     debug!("before display_data()");
-    display_data(&dns_response)?;
+    display_data(dns_response)?;
     debug!("after display_data()");
 
-    Ok(received)
+    Ok(())
 }
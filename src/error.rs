@@ -10,12 +10,19 @@ pub enum DNSError {
     LoggerError(log::SetLoggerError),
     DNS(String),
     DNSInternalError(InternalError),
+    Tls(rustls::Error),
+    Http(Box<ureq::Error>),
+    Quic(quinn::ConnectionError),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum InternalError {
     DnsDomainNameTooLong,
     EmptyDomainName,
+    DnsLabelTooLong,     // a label is limited to 63 octets
+    DnsOffsetOutOfRange, // reading a domain name past the end of the message
+    DnsForwardPointer,   // a compression pointer not pointing to a prior occurrence
+    DnsPointerLoop,      // a compression pointer pointing back into the name being decoded
 }
 
 impl DNSError {
@@ -23,6 +30,29 @@ impl DNSError {
     pub fn new(s: &str) -> Self {
         DNSError::DNS(String::from(s))
     }
+
+    // True if the error is a timeout when waiting for a response
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            DNSError::Io(e) => is_io_timeout(e),
+            // HTTP requests time out while connecting or reading the response
+            DNSError::Http(e) => match e.as_ref() {
+                ureq::Error::Transport(t) => std::error::Error::source(t)
+                    .and_then(|source| source.downcast_ref::<io::Error>())
+                    .is_some_and(is_io_timeout),
+                ureq::Error::Status(..) => false,
+            },
+            DNSError::Quic(e) => matches!(e, quinn::ConnectionError::TimedOut),
+            _ => false,
+        }
+    }
+}
+
+fn is_io_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
 }
 
 /// A specific custom `Result` for all functions
@@ -58,3 +88,21 @@ impl From<log::SetLoggerError> for DNSError {
         DNSError::LoggerError(err)
     }
 }
+
+impl From<rustls::Error> for DNSError {
+    fn from(err: rustls::Error) -> Self {
+        DNSError::Tls(err)
+    }
+}
+
+impl From<ureq::Error> for DNSError {
+    fn from(err: ureq::Error) -> Self {
+        DNSError::Http(Box::new(err))
+    }
+}
+
+impl From<quinn::ConnectionError> for DNSError {
+    fn from(err: quinn::ConnectionError) -> Self {
+        DNSError::Quic(err)
+    }
+}
@@ -0,0 +1,230 @@
+//! The static table of host names: https://man7.org/linux/man-pages/man5/hosts.5.html
+//!
+//! Each line is an address followed by its canonical name and aliases. Addresses and names
+//! found there are given back as resource records, as if a name server had sent them.
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::str::FromStr;
+
+use log::debug;
+
+use crate::error::{DNSError, DNSResult};
+use crate::rfc1035::{DNSResourceRecord, DomainName, QClass, QType, RdData};
+
+// Default location of the table
+pub const HOSTS: &str = "/etc/hosts";
+
+// TTL of the records built from the table: they're not meant to be cached
+const HOSTS_TTL: u32 = 0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostEntry {
+    pub address: IpAddr,
+
+    // the canonical name first, then the aliases
+    pub names: Vec<DomainName>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Hosts {
+    pub entries: Vec<HostEntry>,
+}
+
+impl Hosts {
+    // Read the table from another file than the system one
+    pub fn from_file<P: AsRef<Path>>(path: P) -> DNSResult<Self> {
+        let content = fs::read_to_string(path.as_ref())
+            .map_err(|e| DNSError::new(&format!("{}: {}", path.as_ref().display(), e)))?;
+        Self::from_str(&content)
+    }
+
+    // Read /etc/hosts
+    pub fn system() -> DNSResult<Self> {
+        Self::from_file(HOSTS)
+    }
+
+    // The A or AAAA records of all the lines with the name, or the PTR record of the first line
+    // with the address of a reverse name. Other types are not in the table.
+    pub fn lookup(&self, name: &DomainName, qtype: QType) -> Vec<DNSResourceRecord> {
+        match qtype {
+            QType::A | QType::AAAA => self
+                .entries
+                .iter()
+                .filter(|entry| entry.names.contains(name))
+                .filter_map(|entry| match (qtype, entry.address) {
+                    (QType::A, IpAddr::V4(ip)) => Some((4, RdData::A(u32::from(ip)))),
+                    (QType::AAAA, IpAddr::V6(ip)) => Some((16, RdData::AAAA(ip.octets()))),
+                    _ => None,
+                })
+                .map(|(rd_length, rd_data)| record(name, qtype, rd_length, rd_data))
+                .collect(),
+            QType::PTR => reverse(name)
+                .and_then(|address| self.entries.iter().find(|entry| entry.address == address))
+                .map(|entry| {
+                    let target = entry.names[0].clone();
+                    let rd_length = target.labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1;
+                    record(name, qtype, rd_length as u16, RdData::PTR(target))
+                })
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// ```
+/// use std::str::FromStr;
+/// use dnslib::hosts::Hosts;
+/// use dnslib::rfc1035::{DomainName, QType};
+///
+/// let hosts = Hosts::from_str("127.0.0.1 localhost\n::1 localhost ip6-localhost").unwrap();
+/// let localhost = DomainName::try_from("localhost").unwrap();
+/// assert_eq!(hosts.lookup(&localhost, QType::A).len(), 1);
+/// assert_eq!(hosts.lookup(&localhost, QType::AAAA).len(), 1);
+/// assert!(hosts.lookup(&localhost, QType::MX).is_empty());
+/// ```
+impl FromStr for Hosts {
+    type Err = DNSError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hosts = Hosts::default();
+
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+
+            let Some(address) = words.next() else {
+                continue;
+            };
+            let (Ok(address), Ok(names)) = (
+                IpAddr::from_str(address),
+                words
+                    .map(DomainName::try_from)
+                    .collect::<DNSResult<Vec<_>>>(),
+            ) else {
+                debug!("ignoring hosts line '{}'", line);
+                continue;
+            };
+            if names.is_empty() {
+                debug!("ignoring hosts line '{}'", line);
+                continue;
+            }
+
+            hosts.entries.push(HostEntry { address, names });
+        }
+
+        Ok(hosts)
+    }
+}
+
+fn record(name: &DomainName, r#type: QType, rd_length: u16, rd_data: RdData) -> DNSResourceRecord {
+    DNSResourceRecord {
+        name: name.clone(),
+        r#type,
+        class: QClass::IN,
+        ttl: HOSTS_TTL,
+        rd_length,
+        rd_data: Some(rd_data),
+    }
+}
+
+// The address of a reverse name: 1.0.0.127.in-addr.arpa or the 32 nibbles of an ip6.arpa name
+fn reverse(name: &DomainName) -> Option<IpAddr> {
+    let labels: Vec<_> = name.labels.iter().map(|l| l.to_lowercase()).collect();
+
+    match labels.as_slice() {
+        [digits @ .., in_addr, arpa] if in_addr == "in-addr" && arpa == "arpa" => {
+            if digits.len() != 4 {
+                return None;
+            }
+            let mut octets = [0u8; 4];
+            for (octet, digit) in octets.iter_mut().zip(digits.iter().rev()) {
+                *octet = digit.parse().ok()?;
+            }
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        [nibbles @ .., ip6, arpa] if ip6 == "ip6" && arpa == "arpa" => {
+            if nibbles.len() != 32 {
+                return None;
+            }
+            let mut address = 0u128;
+            for nibble in nibbles.iter().rev() {
+                if nibble.len() != 1 {
+                    return None;
+                }
+                address = address << 4 | u128::from_str_radix(nibble, 16).ok()?;
+            }
+            Some(IpAddr::V6(Ipv6Addr::from(address)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosts() {
+        let hosts = Hosts::from_str(
+            r#"
+# static table
+127.0.0.1	localhost
+::1		localhost ip6-localhost  # loopback
+192.0.2.10 dev.example.com dev
+192.0.2.11 dev.example.com
+fe80::1%eth0 link-local
+not-an-address foo
+192.0.2.12
+"#,
+        )
+        .unwrap();
+        assert_eq!(hosts.entries.len(), 4);
+
+        // names are case insensitive, and all lines count
+        let dev = DomainName::try_from("DEV.example.com").unwrap();
+        let records = hosts.lookup(&dev, QType::A);
+        assert_eq!(records.len(), 2);
+        assert!(matches!(records[1].rd_data, Some(RdData::A(0xC000020B))));
+        assert!(hosts.lookup(&dev, QType::AAAA).is_empty());
+
+        let localhost = DomainName::try_from("ip6-localhost").unwrap();
+        let records = hosts.lookup(&localhost, QType::AAAA);
+        assert!(
+            matches!(records[0].rd_data, Some(RdData::AAAA(ip)) if ip == Ipv6Addr::LOCALHOST.octets())
+        );
+
+        // reverse names give the canonical name of the first line
+        let ptr = DomainName::try_from("11.2.0.192.in-addr.arpa").unwrap();
+        let records = hosts.lookup(&ptr, QType::PTR);
+        assert!(
+            matches!(&records[0].rd_data, Some(RdData::PTR(name)) if name.to_string() == "dev.example.com.")
+        );
+        assert_eq!(records[0].rd_length, 17);
+
+        let ptr = DomainName::try_from(
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.ip6.arpa",
+        )
+        .unwrap();
+        let records = hosts.lookup(&ptr, QType::PTR);
+        assert!(
+            matches!(&records[0].rd_data, Some(RdData::PTR(name)) if name.to_string() == "localhost.")
+        );
+
+        let ptr = DomainName::try_from("1.0.0.10.in-addr.arpa").unwrap();
+        assert!(hosts.lookup(&ptr, QType::PTR).is_empty());
+    }
+
+    #[test]
+    fn from_file() {
+        let path = std::env::temp_dir().join(format!("dnslib-hosts-{}", std::process::id()));
+        fs::write(&path, "192.0.2.1 printer\n").unwrap();
+
+        let hosts = Hosts::from_file(&path).unwrap();
+        assert_eq!(hosts.entries[0].address, IpAddr::from([192, 0, 2, 1]));
+
+        fs::remove_file(&path).unwrap();
+        assert!(Hosts::from_file(&path).is_err());
+    }
+}
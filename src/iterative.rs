@@ -0,0 +1,392 @@
+//! Iterative resolution: starting from the root servers, the NS referrals are followed down to the
+//! servers authoritative for the name, as a recursive server does.
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use log::debug;
+
+use crate::client::{ClientOptions, DNSClient};
+use crate::error::{DNSError, DNSResult};
+use crate::rfc1035::{
+    DNSQuery, DNSQuestion, DNSResourceRecord, DNSResponse, DomainName, QType, RdData, ResponseCode,
+};
+use crate::rfc6891::OPT;
+use crate::transport::DNS_PORT;
+
+// Root servers: https://www.internic.net/domain/named.root
+pub const ROOT_HINTS: [(&str, &str, &str); 13] = [
+    ("a.root-servers.net", "198.41.0.4", "2001:503:ba3e::2:30"),
+    ("b.root-servers.net", "170.247.170.2", "2801:1b8:10::b"),
+    ("c.root-servers.net", "192.33.4.12", "2001:500:2::c"),
+    ("d.root-servers.net", "199.7.91.13", "2001:500:2d::d"),
+    ("e.root-servers.net", "192.203.230.10", "2001:500:a8::e"),
+    ("f.root-servers.net", "192.5.5.241", "2001:500:2f::f"),
+    ("g.root-servers.net", "192.112.36.4", "2001:500:12::d0d"),
+    ("h.root-servers.net", "198.97.190.53", "2001:500:1::53"),
+    ("i.root-servers.net", "192.36.148.17", "2001:7fe::53"),
+    ("j.root-servers.net", "192.58.128.30", "2001:503:c27::2:30"),
+    ("k.root-servers.net", "193.0.14.129", "2001:7fd::1"),
+    ("l.root-servers.net", "199.7.83.42", "2001:500:9f::42"),
+    ("m.root-servers.net", "202.12.27.33", "2001:dc3::35"),
+];
+
+// Upper bound of the queries sent for a resolution, against referral loops
+const MAX_QUERIES: usize = 64;
+
+// Upper bound of nested resolutions of name server addresses
+const MAX_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameServer {
+    pub name: DomainName,
+    pub address: IpAddr,
+}
+
+// One query-response exchange of the resolution
+#[derive(Debug)]
+pub struct Step {
+    // zone the server was asked for
+    pub zone: DomainName,
+    pub server: NameServer,
+    pub response: DNSResponse,
+}
+
+#[derive(Debug)]
+pub struct IterativeResolver {
+    root: Vec<NameServer>,
+    port: u16,
+    options: ClientOptions,
+}
+
+impl IterativeResolver {
+    // Start from the built-in root hints. The options apply to each server queried.
+    pub fn new(options: ClientOptions) -> Self {
+        let mut root = Vec::new();
+        for (name, ipv4, ipv6) in ROOT_HINTS {
+            for address in [ipv4, ipv6] {
+                root.push(NameServer {
+                    name: DomainName::try_from(name).unwrap(),
+                    address: address.parse().unwrap(),
+                });
+            }
+        }
+        Self::with_root(root, DNS_PORT, options)
+    }
+
+    // Start from other root servers, all of them listening on the given port, e.g. for a
+    // private hierarchy
+    pub fn with_root(root: Vec<NameServer>, port: u16, options: ClientOptions) -> Self {
+        Self {
+            root,
+            port,
+            options,
+        }
+    }
+
+    // All the exchanges with the servers down to the final answer, which is the response
+    // of the last step. The resolution of name servers not given with glue is not part of it.
+    pub fn resolve(&self, name: &str, qtype: QType) -> DNSResult<Vec<Step>> {
+        let name = DomainName::try_from(name)?;
+        let mut steps = Vec::new();
+        let mut queries = 0;
+
+        self.iterate(&name, qtype, 0, &mut queries, &mut steps)?;
+        Ok(steps)
+    }
+
+    fn iterate(
+        &self,
+        name: &DomainName,
+        qtype: QType,
+        depth: usize,
+        queries: &mut usize,
+        steps: &mut Vec<Step>,
+    ) -> DNSResult<()> {
+        let mut zone = DomainName::try_from(".")?;
+        let mut servers = self.root.clone();
+
+        loop {
+            // ipv4 servers first, being the most likely to be reachable
+            servers.sort_by_key(|server| server.address.is_ipv6());
+            let (server, response) = self.ask(&servers, name, qtype, queries)?;
+            debug!("{} answered for zone {}", server.name, zone);
+
+            let referral = if response.response_code() == ResponseCode::NoError
+                && response.answer.is_empty()
+                && !response.header.flags.authorative_answer
+            {
+                referral(&response, name, &zone)
+            } else {
+                None
+            };
+
+            steps.push(Step {
+                zone: zone.clone(),
+                server,
+                response,
+            });
+
+            // an answer, an error or a negative answer from the authoritative servers
+            let Some((child, ns_names)) = referral else {
+                return Ok(());
+            };
+            let response = &steps.last().unwrap().response;
+
+            // glue is only trusted for names in the zone of the server
+            servers = ns_names
+                .iter()
+                .filter(|ns| ns.is_subdomain_of(&zone))
+                .flat_map(|ns| glue(response, ns))
+                .collect();
+
+            if servers.is_empty() {
+                servers = self.resolve_servers(&ns_names, depth, queries)?;
+            }
+            zone = child;
+        }
+    }
+
+    // Addresses of the name servers given without glue, found by resolving their names
+    // from the root. The first name resolved is enough.
+    fn resolve_servers(
+        &self,
+        ns_names: &[DomainName],
+        depth: usize,
+        queries: &mut usize,
+    ) -> DNSResult<Vec<NameServer>> {
+        if depth >= MAX_DEPTH {
+            return Err(DNSError::new("too many nested name server resolutions"));
+        }
+
+        for ns in ns_names {
+            debug!("resolving name server {}", ns);
+            let mut steps = Vec::new();
+            if let Err(e) = self.iterate(ns, QType::A, depth + 1, queries, &mut steps) {
+                debug!("unable to resolve {}: {:?}", ns, e);
+                continue;
+            }
+
+            let servers: Vec<_> = steps
+                .last()
+                .map(|step| glue(&step.response, ns))
+                .unwrap_or_default();
+            if !servers.is_empty() {
+                return Ok(servers);
+            }
+        }
+
+        Err(DNSError::new("no address found for the name servers"))
+    }
+
+    // Send the query to the servers in turn, until one answers
+    fn ask(
+        &self,
+        servers: &[NameServer],
+        name: &DomainName,
+        qtype: QType,
+        queries: &mut usize,
+    ) -> DNSResult<(NameServer, DNSResponse)> {
+        let mut query = DNSQuery::default();
+        query.header.flags.recursion_desired = false;
+        query.push_question(DNSQuestion::new(&name.to_string(), qtype, None)?);
+        query.push_additional(DNSResourceRecord::from(OPT::default()));
+
+        let mut error = DNSError::new("no name server to query");
+        for server in servers {
+            if *queries >= MAX_QUERIES {
+                return Err(DNSError::new("too many queries for the resolution"));
+            }
+            *queries += 1;
+
+            let address = SocketAddr::new(server.address, self.port);
+            match DNSClient::new(&address.to_string(), self.options.clone())
+                .and_then(|client| client.query(&query))
+            {
+                Ok(response) => return Ok((server.clone(), response)),
+                Err(e) => {
+                    debug!("{} ({}) didn't answer: {:?}", server.name, address, e);
+                    error = e;
+                }
+            }
+        }
+
+        Err(error)
+    }
+}
+
+// The delegated zone and its name servers, if the response is a referral to a zone closer
+// to the name than the current one
+fn referral(
+    response: &DNSResponse,
+    name: &DomainName,
+    zone: &DomainName,
+) -> Option<(DomainName, Vec<DomainName>)> {
+    let mut child: Option<DomainName> = None;
+    let mut ns_names = Vec::new();
+
+    for rr in &response.authority {
+        let Some(RdData::NS(ns)) = &rr.rd_data else {
+            continue;
+        };
+        let closer = name.is_subdomain_of(&rr.name)
+            && rr.name.is_subdomain_of(zone)
+            && rr.name.labels.len() > zone.labels.len();
+        if !closer || child.as_ref().is_some_and(|child| *child != rr.name) {
+            debug!("ignoring NS record for {}", rr.name);
+            continue;
+        }
+
+        child = Some(rr.name.clone());
+        ns_names.push(ns.clone());
+    }
+
+    child.map(|child| (child, ns_names))
+}
+
+// Addresses of the name server found in the response
+fn glue(response: &DNSResponse, ns: &DomainName) -> Vec<NameServer> {
+    response
+        .answer
+        .iter()
+        .chain(response.additional.iter())
+        .filter(|rr| rr.name == *ns)
+        .filter_map(|rr| match rr.rd_data {
+            Some(RdData::A(ipv4)) => Some(IpAddr::V4(Ipv4Addr::from(ipv4))),
+            Some(RdData::AAAA(ipv6)) => Some(IpAddr::from(ipv6)),
+            _ => None,
+        })
+        .map(|address| NameServer {
+            name: ns.clone(),
+            address,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::UdpSocket;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::network_order::{FromNetworkOrder, ToNetworkOrder};
+    use crate::rfc1035::{DNSMessage, PacketType, QClass};
+
+    fn rr(name: &str, r#type: QType, rd_data: RdData) -> DNSResourceRecord {
+        DNSResourceRecord {
+            name: DomainName::try_from(name).unwrap(),
+            r#type,
+            class: QClass::IN,
+            ttl: 3600,
+            rd_length: 0,
+            rd_data: Some(rd_data),
+        }
+    }
+
+    fn ns(zone: &str, ns: &str) -> DNSResourceRecord {
+        rr(
+            zone,
+            QType::NS,
+            RdData::NS(DomainName::try_from(ns).unwrap()),
+        )
+    }
+
+    fn a(name: &str, ip: [u8; 4]) -> DNSResourceRecord {
+        rr(name, QType::A, RdData::A(u32::from(Ipv4Addr::from(ip))))
+    }
+
+    // a stand-in server on 127.0.0.x, answering from its zone data
+    fn serve(x: u8, port: u16, zone: fn(&str, &mut DNSMessage)) {
+        let udp = UdpSocket::bind((Ipv4Addr::new(127, 0, 0, x), port)).unwrap();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            loop {
+                let (received, from) = udp.recv_from(&mut buffer).unwrap();
+                let mut message = DNSMessage::default();
+                message
+                    .from_network_bytes(&mut Cursor::new(&buffer[..received]))
+                    .unwrap();
+                message.header.flags.packet_type = PacketType::Response;
+
+                let name = message.question[0].name.to_string();
+                zone(&name, &mut message);
+
+                let mut response = Vec::new();
+                message.to_network_bytes(&mut response).unwrap();
+                udp.send_to(&response, from).unwrap();
+            }
+        });
+    }
+
+    // root (127.0.0.1) delegates com. (127.0.0.2) and net. (127.0.0.4). example.com. is
+    // served by ns1.dns-host.net. (127.0.0.3), for which com. gives no usable glue.
+    fn hierarchy() -> u16 {
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        serve(1, port, |name, message| {
+            if name.ends_with("com.") {
+                message.push_authority(ns("com", "a.nic.com"));
+                message.push_additional(a("a.nic.com", [127, 0, 0, 2]));
+            } else {
+                message.push_authority(ns("net", "a.nic.net"));
+                message.push_additional(a("a.nic.net", [127, 0, 0, 4]));
+            }
+        });
+        serve(2, port, |_, message| {
+            message.push_authority(ns("example.com", "ns1.dns-host.net"));
+            // out of bailiwick, not to be trusted
+            message.push_additional(a("ns1.dns-host.net", [192, 0, 2, 66]));
+        });
+        serve(3, port, |name, message| {
+            message.header.flags.authorative_answer = true;
+            if name == "www.example.com." {
+                message.push_answer(a(name, [192, 0, 2, 80]));
+            } else {
+                message.set_response_code(ResponseCode::NXDomain);
+            }
+        });
+        serve(4, port, |name, message| {
+            message.header.flags.authorative_answer = true;
+            message.push_answer(a(name, [127, 0, 0, 3]));
+        });
+
+        port
+    }
+
+    #[test]
+    fn iterative_resolution() {
+        let port = hierarchy();
+        let root = vec![NameServer {
+            name: DomainName::try_from("a.root").unwrap(),
+            address: IpAddr::from([127, 0, 0, 1]),
+        }];
+        let options = ClientOptions {
+            timeout: Duration::from_millis(500),
+            retries: 0,
+            ..ClientOptions::default()
+        };
+        let resolver = IterativeResolver::with_root(root, port, options);
+
+        let steps = resolver.resolve("www.example.com", QType::A).unwrap();
+        let zones: Vec<_> = steps.iter().map(|step| step.zone.to_string()).collect();
+        assert_eq!(zones, vec![".", "com.", "example.com."]);
+
+        let last = steps.last().unwrap();
+        assert_eq!(last.server.name.to_string(), "ns1.dns-host.net.");
+        assert_eq!(last.server.address, IpAddr::from([127, 0, 0, 3]));
+        assert!(matches!(
+            last.response.answer[0].rd_data,
+            Some(RdData::A(ip)) if Ipv4Addr::from(ip) == Ipv4Addr::new(192, 0, 2, 80)
+        ));
+
+        // a negative answer ends the resolution too
+        let steps = resolver.resolve("nowhere.example.com", QType::A).unwrap();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[2].response.response_code(), ResponseCode::NXDomain);
+    }
+}
@@ -1,7 +1,16 @@
+#[cfg(feature = "async")]
+pub mod async_client;
+pub mod client;
 pub mod error;
+pub mod hosts;
+pub mod iterative;
 pub mod macros;
 pub mod network_order;
 //pub mod query;
+pub mod resolv_conf;
+pub mod resolver;
 pub mod rfc1035;
+pub mod rfc6891;
+pub mod transport;
 pub mod util;
 //pub mod response;
@@ -4,7 +4,7 @@ macro_rules! derive_enum {
     ($t:ty, u8) => {
         impl ToNetworkOrder for $t {
             fn to_network_bytes(&self, v: &mut Vec<u8>) -> std::io::Result<usize> {
-                v.write_u8(*self as u8)?;
+                v.write_u8(u16::from(*self) as u8)?;
                 Ok(1)
             }
         }
@@ -26,7 +26,7 @@ macro_rules! derive_enum {
     ($t:ty, u16) => {
         impl ToNetworkOrder for $t {
             fn to_network_bytes(&self, v: &mut Vec<u8>) -> std::io::Result<usize> {
-                v.write_u16::<BigEndian>(*self as u16)?;
+                v.write_u16::<BigEndian>(u16::from(*self))?;
                 Ok(2)
             }
         }
@@ -50,7 +50,7 @@ macro_rules! derive_enum {
 #[macro_export]
 macro_rules! test_from_network {
     ($slice:ident, $t:ty) => {{
-        let s = crate::util::get_sample_slice($slice);
+        let s = $crate::util::get_sample_slice($slice);
         let mut buffer = std::io::Cursor::new(s.as_slice());
         let mut v = <$t>::default();
         assert!(v.from_network_bytes(&mut buffer).is_ok());
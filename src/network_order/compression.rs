@@ -0,0 +1,64 @@
+//! Domain name compression when writing messages: https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.4
+use std::collections::HashMap;
+
+// pointers are 14-bit offsets
+const MAX_POINTER_OFFSET: usize = 0x3FFF;
+
+/// Keep track of the domain names already written in a message, so that the same suffix
+/// can be replaced by a pointer to its prior occurrence.
+#[derive(Debug, Default)]
+pub struct CompressionTable {
+    // position of the message first byte in the buffer
+    base: usize,
+
+    // lowercased domain name suffix labels, and its offset from the start of the message.
+    // Labels are kept apart because a label read from the wire can contain a dot.
+    suffixes: HashMap<Vec<String>, u16>,
+}
+
+impl CompressionTable {
+    /// Create a new table for a message starting at position `base` in the buffer.
+    ///
+    /// ```
+    /// use dnslib::network_order::compression::CompressionTable;
+    ///
+    /// let mut table = CompressionTable::new(2);
+    /// assert!(table.insert(&["www", "example", "com"], 14));
+    /// assert_eq!(table.find(&["www", "EXAMPLE", "com"]), Some(12));
+    /// assert_eq!(table.find(&["example", "com"]), None);
+    ///
+    /// // a dot inside a label doesn't make it several labels
+    /// assert!(table.insert(&["a.b", "c"], 40));
+    /// assert_eq!(table.find(&["a", "b", "c"]), None);
+    /// ```
+    pub fn new(base: usize) -> Self {
+        Self {
+            base,
+            suffixes: HashMap::new(),
+        }
+    }
+
+    // domain names are case insensitive
+    fn key(labels: &[impl AsRef<str>]) -> Vec<String> {
+        labels.iter().map(|l| l.as_ref().to_lowercase()).collect()
+    }
+
+    /// Return the offset of a previously written domain name suffix, if any
+    pub fn find(&self, labels: &[impl AsRef<str>]) -> Option<u16> {
+        self.suffixes.get(&Self::key(labels)).copied()
+    }
+
+    /// Record the domain name suffix written at `position` in the buffer. Returns false if
+    /// the suffix can't be pointed to, because it's too far from the message start.
+    pub fn insert(&mut self, labels: &[impl AsRef<str>], position: usize) -> bool {
+        let offset = position - self.base;
+        if offset > MAX_POINTER_OFFSET {
+            return false;
+        }
+
+        self.suffixes
+            .entry(Self::key(labels))
+            .or_insert(offset as u16);
+        true
+    }
+}
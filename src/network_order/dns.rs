@@ -1,15 +1,17 @@
 //! All functions/trait to convert DNS structures to network order back & forth
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Cursor, Result, Seek, SeekFrom};
+use std::io::{Cursor, Result};
 
 use crate::derive_enum;
 use crate::error::{DNSError, DNSResult};
-use crate::network_order::{FromNetworkOrder, ToNetworkOrder};
+use crate::network_order::{
+    compression::CompressionTable, read_slice, FromNetworkOrder, ToNetworkOrder,
+};
 use crate::rfc1035::{
     CharacterString, DNSPacketFlags, DomainName, OpCode, PacketType, QClass, QType, ResponseCode,
 };
 
-impl<'a> ToNetworkOrder for CharacterString {
+impl ToNetworkOrder for CharacterString {
     /// ```
     /// use dnslib::rfc1035::CharacterString;
     /// use dnslib::network_order::ToNetworkOrder;
@@ -39,23 +41,16 @@ impl<'a> FromNetworkOrder<'a> for CharacterString {
     /// assert!(cs.from_network_bytes(&mut buffer).is_ok());
     /// assert_eq!(cs.length, 6u8);
     /// assert_eq!(cs.data, "google");
+    /// assert_eq!(buffer.position(), 7);
+    ///
+    /// // the string is cut short
+    /// let mut buffer = Cursor::new([0x06_u8, 0x67, 0x6f].as_slice());
+    /// assert!(cs.from_network_bytes(&mut buffer).is_err());
     /// ```    
     fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
-        // get a reference on [u8]
-        let position = buffer.position() as usize;
-        let inner_data = buffer.get_ref();
-
-        // first char is the string length
-        self.length = inner_data[position] as u8;
-
-        // move the cursor forward
-        buffer.seek(SeekFrom::Current(self.length as i64))?;
-
-        // save data
-        self.data = String::from_utf8_lossy(
-            &buffer.get_ref()[position + 1..position + self.length as usize + 1],
-        )
-        .to_string();
+        // first char is the string length, followed by the string itself
+        self.length = read_slice(buffer, 1)?[0];
+        self.data = String::from_utf8_lossy(read_slice(buffer, self.length as u16)?).to_string();
         Ok(())
     }
 }
@@ -84,6 +79,53 @@ impl ToNetworkOrder for DomainName {
         length += 0_u8.to_network_bytes(buffer)?;
         Ok(length)
     }
+
+    /// ```
+    /// use dnslib::rfc1035::DomainName;
+    /// use dnslib::network_order::{compression::CompressionTable, ToNetworkOrder};
+    ///
+    /// let mut buffer: Vec<u8> = vec![0xFF; 12];
+    /// let mut table = CompressionTable::new(0);
+    ///
+    /// let dn = DomainName::try_from("www.google.ie").unwrap();
+    /// assert_eq!(dn.to_network_bytes_compressed(&mut buffer, &mut table).unwrap(), 15);
+    ///
+    /// // suffix google.ie is found at offset 16
+    /// let dn = DomainName::try_from("mail.google.ie").unwrap();
+    /// assert_eq!(dn.to_network_bytes_compressed(&mut buffer, &mut table).unwrap(), 7);
+    /// assert_eq!(&buffer[27..], &[0x04, 0x6d, 0x61, 0x69, 0x6c, 0xC0, 0x10]);
+    ///
+    /// // same domain name is just a pointer
+    /// let dn = DomainName::try_from("WWW.google.ie").unwrap();
+    /// assert_eq!(dn.to_network_bytes_compressed(&mut buffer, &mut table).unwrap(), 2);
+    /// assert_eq!(&buffer[34..], &[0xC0, 0x0C]);
+    /// ```
+    fn to_network_bytes_compressed(
+        &self,
+        buffer: &mut Vec<u8>,
+        table: &mut CompressionTable,
+    ) -> Result<usize> {
+        let mut length = 0usize;
+
+        for (i, label) in self.labels.iter().enumerate() {
+            // if the remaining labels were already written, point to them
+            if let Some(offset) = table.find(&self.labels[i..]) {
+                length += (0xC000 | offset).to_network_bytes(buffer)?;
+                return Ok(length);
+            }
+
+            // otherwise, this suffix can be pointed to by further names
+            table.insert(&self.labels[i..], buffer.len());
+
+            // write label: length first, and then chars
+            length += (label.len() as u8).to_network_bytes(buffer)?;
+            length += label.as_str().to_network_bytes(buffer)?;
+        }
+
+        // trailing 0 means end of domain name
+        length += 0_u8.to_network_bytes(buffer)?;
+        Ok(length)
+    }
 }
 
 impl<'a> FromNetworkOrder<'a> for DomainName {
@@ -100,8 +142,6 @@ impl<'a> FromNetworkOrder<'a> for DomainName {
     /// assert_eq!(dn.labels, &["www", "google", "ie"]);
     /// ```    
     fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
-        //dbg!("============================");
-
         // loop through the vector
         let start_position = buffer.position() as usize;
 
@@ -113,9 +153,6 @@ impl<'a> FromNetworkOrder<'a> for DomainName {
 
         // set new position
         buffer.set_position(new_position as u64);
-        println!("domain============>{}, new_pos={}", self, new_position);
-
-        // if a pointer, get pointer value and call
         Ok(())
     }
 }
@@ -165,7 +202,7 @@ impl ToNetworkOrder for DNSPacketFlags {
         flags |= (self.z as u16) << 6;
         flags |= (self.authentic_data as u16) << 5;
         flags |= (self.checking_disabled as u16) << 4;
-        flags |= self.response_code as u16;
+        flags |= u16::from(self.response_code) & 0b1111;
 
         buffer.write_u16::<BigEndian>(flags)?;
         Ok(2)
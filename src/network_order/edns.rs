@@ -0,0 +1,223 @@
+//! Conversion of EDNS structures to network order back & forth
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Cursor, Result};
+
+use crate::derive_enum;
+use crate::error::{DNSError, DNSResult};
+use crate::network_order::{read_slice, FromNetworkOrder, ToNetworkOrder};
+use crate::rfc6891::{
+    ClientSubnet, Cookie, EdnsOption, ExtendedError, InfoCode, OptionCode, Padding,
+    CLIENT_COOKIE_LENGTH, MAX_SERVER_COOKIE_LENGTH, MIN_SERVER_COOKIE_LENGTH, OPT,
+};
+
+derive_enum!(OptionCode, u16);
+derive_enum!(InfoCode, u16);
+
+// Only the RDATA is written, the other OPT fields are part of the RR
+impl ToNetworkOrder for OPT {
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> Result<usize> {
+        self.options.to_network_bytes(buffer)
+    }
+}
+
+// The buffer only holds the RDATA: options are read until its end
+impl<'a> FromNetworkOrder<'a> for OPT {
+    fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
+        while (buffer.position() as usize) < buffer.get_ref().len() {
+            let mut option = EdnsOption::default();
+            option.from_network_bytes(buffer)?;
+            self.options.push(option);
+        }
+
+        Ok(())
+    }
+}
+
+impl ToNetworkOrder for EdnsOption {
+    /// ```
+    /// use dnslib::rfc6891::EdnsOption;
+    /// use dnslib::network_order::ToNetworkOrder;
+    ///
+    /// let option = EdnsOption::Unknown { option_code: 65001, bytes: vec![0xde, 0xad] };
+    /// let mut buffer: Vec<u8> = Vec::new();
+    ///
+    /// assert_eq!(option.to_network_bytes(&mut buffer).unwrap(), 6);
+    /// assert_eq!(&buffer, &[0xFD, 0xE9, 0x00, 0x02, 0xde, 0xad]);
+    /// ```
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> Result<usize> {
+        let mut length = self.code().to_network_bytes(buffer)?;
+
+        // OPTION-LENGTH is only known once OPTION-DATA is written
+        let option_length_position = buffer.len();
+        length += 0_u16.to_network_bytes(buffer)?;
+
+        let option_length = match self {
+            EdnsOption::ClientSubnet(ecs) => ecs.to_network_bytes(buffer)?,
+            EdnsOption::Cookie(cookie) => cookie.to_network_bytes(buffer)?,
+            EdnsOption::ExtendedError(ede) => ede.to_network_bytes(buffer)?,
+            EdnsOption::Nsid(nsid) => nsid.as_slice().to_network_bytes(buffer)?,
+            EdnsOption::Padding(padding) => padding.to_network_bytes(buffer)?,
+            EdnsOption::TcpKeepalive(timeout) => timeout.to_network_bytes(buffer)?,
+            EdnsOption::Unknown { bytes, .. } => bytes.as_slice().to_network_bytes(buffer)?,
+        };
+        let option_length = u16::try_from(option_length).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "OPTION-DATA too long for OPTION-LENGTH",
+            )
+        })?;
+        buffer[option_length_position..option_length_position + 2]
+            .copy_from_slice(&option_length.to_be_bytes());
+
+        Ok(length + option_length as usize)
+    }
+}
+
+impl<'a> FromNetworkOrder<'a> for EdnsOption {
+    /// ```
+    /// use std::io::Cursor;
+    /// use dnslib::rfc6891::EdnsOption;
+    /// use dnslib::network_order::FromNetworkOrder;
+    ///
+    /// let b = vec![0xFD, 0xE9, 0x00, 0x02, 0xde, 0xad];
+    /// let mut buffer = Cursor::new(b.as_slice());
+    /// let mut option = EdnsOption::default();
+    /// assert!(option.from_network_bytes(&mut buffer).is_ok());
+    /// assert_eq!(option, EdnsOption::Unknown { option_code: 65001, bytes: vec![0xde, 0xad] });
+    ///
+    /// // OPTION-DATA is shorter than OPTION-LENGTH
+    /// let b = vec![0xFD, 0xE9, 0x00, 0x04, 0xde, 0xad];
+    /// let mut buffer = Cursor::new(b.as_slice());
+    /// assert!(option.from_network_bytes(&mut buffer).is_err());
+    /// ```
+    fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
+        let option_code = buffer.read_u16::<BigEndian>()?;
+        let option_length = buffer.read_u16::<BigEndian>()?;
+
+        // OPTION-DATA is decoded on its own, so an option can't read beyond its length
+        let data = read_slice(buffer, option_length)?;
+        let mut cursor = Cursor::new(data);
+
+        *self = match OptionCode::try_from(option_code) {
+            Ok(OptionCode::ECS) => {
+                let mut ecs = ClientSubnet::default();
+                ecs.from_network_bytes(&mut cursor)?;
+                EdnsOption::ClientSubnet(ecs)
+            }
+            Ok(OptionCode::COOKIE) => {
+                let mut cookie = Cookie::default();
+                cookie.from_network_bytes(&mut cursor)?;
+                EdnsOption::Cookie(cookie)
+            }
+            Ok(OptionCode::EDE) => {
+                let mut ede = ExtendedError::default();
+                ede.from_network_bytes(&mut cursor)?;
+                EdnsOption::ExtendedError(ede)
+            }
+            Ok(OptionCode::NSID) => EdnsOption::Nsid(data.to_vec()),
+            Ok(OptionCode::PADDING) => EdnsOption::Padding(Padding {
+                length: option_length,
+                block_length: None,
+            }),
+            Ok(OptionCode::KEEPALIVE) => match option_length {
+                0 => EdnsOption::TcpKeepalive(None),
+                2 => {
+                    let mut timeout = 0u16;
+                    timeout.from_network_bytes(&mut cursor)?;
+                    EdnsOption::TcpKeepalive(Some(timeout))
+                }
+                _ => {
+                    return Err(DNSError::new(&format!(
+                        "invalid edns-tcp-keepalive length {}",
+                        option_length
+                    )))
+                }
+            },
+            _ => EdnsOption::Unknown {
+                option_code,
+                bytes: data.to_vec(),
+            },
+        };
+        Ok(())
+    }
+}
+
+impl ToNetworkOrder for ClientSubnet {
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> Result<usize> {
+        let mut length = self.family.to_network_bytes(buffer)?;
+        length += self.source_prefix_length.to_network_bytes(buffer)?;
+        length += self.scope_prefix_length.to_network_bytes(buffer)?;
+        length += self.address.as_slice().to_network_bytes(buffer)?;
+        Ok(length)
+    }
+}
+
+// The buffer only holds the OPTION-DATA: the address spans until its end
+impl<'a> FromNetworkOrder<'a> for ClientSubnet {
+    fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
+        self.family.from_network_bytes(buffer)?;
+        self.source_prefix_length.from_network_bytes(buffer)?;
+        self.scope_prefix_length.from_network_bytes(buffer)?;
+
+        let remaining = buffer.get_ref().len() - buffer.position() as usize;
+        self.address = read_slice(buffer, remaining as u16)?.to_vec();
+        Ok(())
+    }
+}
+
+impl ToNetworkOrder for Cookie {
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> Result<usize> {
+        let mut length = self.client.to_network_bytes(buffer)?;
+        length += self.server.as_slice().to_network_bytes(buffer)?;
+        Ok(length)
+    }
+}
+
+// The buffer only holds the OPTION-DATA: the server cookie spans until its end
+impl<'a> FromNetworkOrder<'a> for Cookie {
+    fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
+        let length = buffer.get_ref().len() - buffer.position() as usize;
+        let server_length = length.saturating_sub(CLIENT_COOKIE_LENGTH);
+        if length < CLIENT_COOKIE_LENGTH
+            || (server_length != 0
+                && !(MIN_SERVER_COOKIE_LENGTH..=MAX_SERVER_COOKIE_LENGTH).contains(&server_length))
+        {
+            return Err(DNSError::new(&format!("invalid cookie length {}", length)));
+        }
+
+        self.client.from_network_bytes(buffer)?;
+        self.server = read_slice(buffer, server_length as u16)?.to_vec();
+        Ok(())
+    }
+}
+
+impl ToNetworkOrder for ExtendedError {
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> Result<usize> {
+        let mut length = self.info_code.to_network_bytes(buffer)?;
+        length += self.extra_text.as_str().to_network_bytes(buffer)?;
+        Ok(length)
+    }
+}
+
+// The buffer only holds the OPTION-DATA: the extra text spans until its end
+impl<'a> FromNetworkOrder<'a> for ExtendedError {
+    fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
+        self.info_code.from_network_bytes(buffer)?;
+
+        // the text should not be NUL terminated, but some servers do it anyway
+        let remaining = buffer.get_ref().len() - buffer.position() as usize;
+        let text = read_slice(buffer, remaining as u16)?;
+        self.extra_text = String::from_utf8_lossy(text)
+            .trim_end_matches('\0')
+            .to_string();
+        Ok(())
+    }
+}
+
+// Zeros only: the length has been set when the message was written
+impl ToNetworkOrder for Padding {
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> Result<usize> {
+        buffer.resize(buffer.len() + self.length as usize, 0);
+        Ok(self.length as usize)
+    }
+}
@@ -1,19 +1,48 @@
 use std::io::{Cursor, Result};
 
-use crate::error::DNSResult;
+use crate::error::{DNSError, DNSResult, InternalError};
+
+use compression::CompressionTable;
 
 // functions to convert to network order (big-endian)
 pub trait ToNetworkOrder: std::fmt::Debug {
     // copy structure data to a network-order buffer
     fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> Result<usize>;
+
+    // same but domain names are compressed using the names already written in the message
+    // and kept in the compression table. Only domain names and structures containing them
+    // need to implement it
+    fn to_network_bytes_compressed(
+        &self,
+        buffer: &mut Vec<u8>,
+        _table: &mut CompressionTable,
+    ) -> Result<usize> {
+        self.to_network_bytes(buffer)
+    }
 }
 
 // functions to convert or build TLS structures
 pub trait FromNetworkOrder<'a>: std::fmt::Debug {
     // copy from a network-order buffer to a structure
+    #[allow(clippy::wrong_self_convention)]
     fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()>;
 }
 
+// Return the next length bytes of the buffer, and move past them
+pub(crate) fn read_slice<'a>(buffer: &mut Cursor<&'a [u8]>, length: u16) -> DNSResult<&'a [u8]> {
+    let bytes: &'a [u8] = buffer.get_ref();
+    let start = buffer.position() as usize;
+    let end = start + length as usize;
+
+    let slice = bytes.get(start..end).ok_or(DNSError::DNSInternalError(
+        InternalError::DnsOffsetOutOfRange,
+    ))?;
+    buffer.set_position(end as u64);
+    Ok(slice)
+}
+
+pub mod compression;
 pub mod dns;
+pub mod edns;
 pub mod primitive;
 //pub mod resource_record;
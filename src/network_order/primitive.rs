@@ -1,9 +1,11 @@
 //! All functions/trait to convert DNS structures to network order back & forth
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Cursor, Result, Seek, SeekFrom};
+use std::io::{Cursor, Result};
 
 use crate::error::DNSResult;
-use crate::network_order::{FromNetworkOrder, ToNetworkOrder};
+use crate::network_order::{
+    compression::CompressionTable, read_slice, FromNetworkOrder, ToNetworkOrder,
+};
 
 impl ToNetworkOrder for u8 {
     /// ```
@@ -150,7 +152,7 @@ impl<'a> FromNetworkOrder<'a> for &[u8] {
     }
 }
 
-impl<'a> ToNetworkOrder for &'a str {
+impl ToNetworkOrder for &str {
     /// ```
     /// use dnslib::network_order::ToNetworkOrder;
     ///
@@ -186,21 +188,25 @@ impl ToNetworkOrder for String {
 }
 
 impl<'a> FromNetworkOrder<'a> for String {
-    fn from_network_bytes(&mut self, buffer: &mut Cursor<&[u8]>) -> DNSResult<()> {
-        // get a reference on [u8]
-        let position = buffer.position() as usize;
-        let inner_data = buffer.get_ref();
-
-        // first char is the string length
-        let length = inner_data[position] as u8;
-
-        // move the cursor forward
-        buffer.seek(SeekFrom::Current(length as i64))?;
-
-        // save data
-        self.push_str(std::str::from_utf8(
-            &buffer.get_ref()[position + 1..position + length as usize + 1],
-        )?);
+    /// ```
+    /// use std::io::Cursor;
+    /// use dnslib::network_order::FromNetworkOrder;
+    ///
+    /// let mut buffer = Cursor::new([0x03_u8, 0x77, 0x78, 0x79].as_slice());
+    /// let mut s = String::new();
+    /// assert!(s.from_network_bytes(&mut buffer).is_ok());
+    /// assert_eq!(s, "wxy");
+    ///
+    /// // the string is cut short, or even its length
+    /// let mut buffer = Cursor::new([0x03_u8, 0x77].as_slice());
+    /// assert!(String::new().from_network_bytes(&mut buffer).is_err());
+    /// let mut buffer = Cursor::new([].as_slice());
+    /// assert!(String::new().from_network_bytes(&mut buffer).is_err());
+    /// ```
+    fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
+        // first char is the string length, followed by the string itself
+        let length = read_slice(buffer, 1)?[0];
+        self.push_str(std::str::from_utf8(read_slice(buffer, length as u16)?)?);
         Ok(())
     }
 }
@@ -225,6 +231,17 @@ impl<T: ToNetworkOrder> ToNetworkOrder for Option<T> {
             self.as_ref().unwrap().to_network_bytes(buffer)
         }
     }
+
+    fn to_network_bytes_compressed(
+        &self,
+        buffer: &mut Vec<u8>,
+        table: &mut CompressionTable,
+    ) -> Result<usize> {
+        match self {
+            None => Ok(0),
+            Some(x) => x.to_network_bytes_compressed(buffer, table),
+        }
+    }
 }
 
 impl<'a, T: FromNetworkOrder<'a>> FromNetworkOrder<'a> for Option<T> {
@@ -323,6 +340,21 @@ where
 
         Ok(length)
     }
+
+    fn to_network_bytes_compressed(
+        &self,
+        buffer: &mut Vec<u8>,
+        table: &mut CompressionTable,
+    ) -> Result<usize> {
+        let mut length = 0usize;
+
+        // copy data for each element
+        for item in self {
+            length += item.to_network_bytes_compressed(buffer, table)?;
+        }
+
+        Ok(length)
+    }
 }
 
 impl<'a, T> FromNetworkOrder<'a> for Vec<T>
@@ -370,23 +402,26 @@ impl ToNetworkOrder for Vec<Box<dyn ToNetworkOrder>> {
 
         Ok(length)
     }
-}
 
-impl<'a> FromNetworkOrder<'a> for Vec<Box<dyn FromNetworkOrder<'a>>> {
-    fn from_network_bytes(&mut self, _buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
-        unimplemented!("Vec<Box<dyn ToFromNetworkOrder<'a>>>.from_network_bytes()");
-        //Ok(())
-    }
-}
+    fn to_network_bytes_compressed(
+        &self,
+        buffer: &mut Vec<u8>,
+        table: &mut CompressionTable,
+    ) -> Result<usize> {
+        let mut length = 0usize;
+
+        // copy data for each element
+        for item in self {
+            length += item.to_network_bytes_compressed(buffer, table)?;
+        }
 
-impl<'a, T> ToNetworkOrder for std::marker::PhantomData<&'a T> {
-    fn to_network_bytes(&self, _buffer: &mut Vec<u8>) -> Result<usize> {
-        Ok(0)
+        Ok(length)
     }
 }
 
-impl<'a, T> FromNetworkOrder<'a> for std::marker::PhantomData<&'a T> {
+impl<'a> FromNetworkOrder<'a> for Vec<Box<dyn FromNetworkOrder<'a>>> {
     fn from_network_bytes(&mut self, _buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
-        Ok(())
+        unimplemented!("Vec<Box<dyn ToFromNetworkOrder<'a>>>.from_network_bytes()");
+        //Ok(())
     }
 }
@@ -0,0 +1,251 @@
+//! The system resolver configuration: https://man7.org/linux/man-pages/man5/resolv.conf.5.html
+//!
+//! Only the keywords of use to a stub resolver are kept, others are ignored as the C library does.
+use std::fs;
+use std::net::{IpAddr, SocketAddr, SocketAddrV6};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::{debug, warn};
+
+use crate::client::ClientOptions;
+use crate::error::{DNSError, DNSResult};
+use crate::transport::DNS_PORT;
+
+// Default location of the configuration
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+// Upper bounds of the options, as enforced by glibc
+const MAX_NDOTS: u8 = 15;
+const MAX_TIMEOUT: u64 = 30;
+const MAX_ATTEMPTS: u8 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvConf {
+    // name servers, in the order they are tried, on the DNS port. Link-local IPv6 addresses
+    // keep their scope: fe80::1%eth0
+    pub nameservers: Vec<SocketAddr>,
+
+    // domains appended to relative names. The domain and search keywords are mutually
+    // exclusive, the last one wins: domain is a search list of one name.
+    pub search: Vec<String>,
+
+    // names with at least that many dots are first tried as absolute names
+    pub ndots: u8,
+
+    // time to wait for a response from a server
+    pub timeout: Duration,
+
+    // number of times the servers are asked
+    pub attempts: u8,
+
+    // the servers are used in turn instead of always starting with the first one
+    pub rotate: bool,
+
+    // queries are sent with an OPT record
+    pub edns0: bool,
+}
+
+impl Default for ResolvConf {
+    fn default() -> Self {
+        Self {
+            nameservers: Vec::new(),
+            search: Vec::new(),
+            ndots: 1,
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            rotate: false,
+            edns0: false,
+        }
+    }
+}
+
+impl ResolvConf {
+    // Read the configuration from another file than the system one
+    pub fn from_file<P: AsRef<Path>>(path: P) -> DNSResult<Self> {
+        let content = fs::read_to_string(path.as_ref())
+            .map_err(|e| DNSError::new(&format!("{}: {}", path.as_ref().display(), e)))?;
+        Self::from_str(&content)
+    }
+
+    // Read /etc/resolv.conf
+    pub fn system() -> DNSResult<Self> {
+        Self::from_file(RESOLV_CONF)
+    }
+
+    // Client timeout and retries. Other settings are not used by the client: it only talks to
+    // a single server, so rotate is up to the caller picking it among the name servers (dnsquery
+    // always takes the first one), and OPT is sent whatever edns0.
+    pub fn client_options(&self) -> ClientOptions {
+        ClientOptions {
+            timeout: self.timeout,
+            retries: self.attempts.saturating_sub(1),
+            ..ClientOptions::default()
+        }
+    }
+
+    // Set an option from the options line, e.g.: ndots:2
+    fn set_option(&mut self, option: &str) {
+        let (name, value) = match option.split_once(':') {
+            Some((name, value)) => (name, value.parse::<u64>().ok()),
+            None => (option, None),
+        };
+
+        match (name, value) {
+            ("ndots", Some(n)) => self.ndots = n.min(MAX_NDOTS as u64) as u8,
+            ("timeout", Some(n)) => self.timeout = Duration::from_secs(n.clamp(1, MAX_TIMEOUT)),
+            ("attempts", Some(n)) => self.attempts = n.clamp(1, MAX_ATTEMPTS as u64) as u8,
+            ("rotate", None) => self.rotate = true,
+            ("edns0", None) => self.edns0 = true,
+            _ => debug!("ignoring resolv.conf option '{}'", option),
+        }
+    }
+}
+
+/// ```
+/// use std::str::FromStr;
+/// use dnslib::resolv_conf::ResolvConf;
+///
+/// let conf = ResolvConf::from_str("nameserver 192.0.2.1\nsearch example.com\noptions ndots:2").unwrap();
+/// assert_eq!(conf.nameservers[0].to_string(), "192.0.2.1:53");
+/// assert_eq!(conf.search, vec!["example.com"]);
+/// assert_eq!(conf.ndots, 2);
+/// ```
+impl FromStr for ResolvConf {
+    type Err = DNSError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut conf = ResolvConf::default();
+
+        for line in s.lines() {
+            // comments start with # or ;
+            let line = line.split(['#', ';']).next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                Some("nameserver") => match words.next().and_then(nameserver) {
+                    Some(address) => conf.nameservers.push(address),
+                    None => warn!("ignoring resolv.conf line '{}'", line),
+                },
+                Some("domain") => conf.search = words.take(1).map(String::from).collect(),
+                Some("search") => conf.search = words.map(String::from).collect(),
+                Some("options") => words.for_each(|option| conf.set_option(option)),
+                Some(_) => debug!("ignoring resolv.conf line '{}'", line),
+                None => (),
+            }
+        }
+
+        Ok(conf)
+    }
+}
+
+// The address of a name server, with the scope of a link-local IPv6 address given as an
+// interface index or name
+fn nameserver(address: &str) -> Option<SocketAddr> {
+    let (ip, scope) = match address.split_once('%') {
+        Some((ip, scope)) => (ip, Some(scope)),
+        None => (address, None),
+    };
+
+    match (IpAddr::from_str(ip).ok()?, scope) {
+        (ip, None) => Some(SocketAddr::new(ip, DNS_PORT)),
+        (IpAddr::V6(ip), Some(scope)) => {
+            let scope_id = scope
+                .parse::<u32>()
+                .ok()
+                .or_else(|| interface_index(scope))?;
+            Some(SocketAddr::V6(SocketAddrV6::new(ip, DNS_PORT, 0, scope_id)))
+        }
+        (IpAddr::V4(_), Some(_)) => None,
+    }
+}
+
+// Index of a network interface, as found in sysfs
+fn interface_index(name: &str) -> Option<u32> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return None;
+    }
+    let index = fs::read_to_string(format!("/sys/class/net/{}/ifindex", name)).ok()?;
+    index.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolv_conf() {
+        let conf = ResolvConf::from_str(
+            r#"
+# generated by NetworkManager
+domain corp.example.com
+search example.com example.net   ; trailing comment
+nameserver 192.0.2.53
+nameserver 2001:db8::53
+nameserver fe80::1%2
+nameserver fe80::53%no-such-interface
+nameserver 192.0.2.1%2
+nameserver
+sortlist 130.155.160.0/255.255.240.0
+options ndots:3 timeout:2 attempts:9 rotate edns0 trust-ad
+options timeout:0
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            conf.nameservers,
+            vec![
+                SocketAddr::from_str("192.0.2.53:53").unwrap(),
+                SocketAddr::from_str("[2001:db8::53]:53").unwrap(),
+                SocketAddr::from_str("[fe80::1%2]:53").unwrap(),
+            ]
+        );
+        assert_eq!(conf.search, vec!["example.com", "example.net"]);
+        assert_eq!(conf.ndots, 3);
+        assert_eq!(conf.timeout, Duration::from_secs(1));
+        assert_eq!(conf.attempts, MAX_ATTEMPTS);
+        assert!(conf.rotate);
+        assert!(conf.edns0);
+
+        let options = conf.client_options();
+        assert_eq!(options.timeout, Duration::from_secs(1));
+        assert_eq!(options.retries, 4);
+
+        // the last of domain and search wins
+        let conf =
+            ResolvConf::from_str("search example.com example.net\ndomain example.org").unwrap();
+        assert_eq!(conf.search, vec!["example.org"]);
+
+        // defaults
+        assert_eq!(ResolvConf::from_str("").unwrap(), ResolvConf::default());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn link_local_nameserver() {
+        // the loopback interface is always there
+        let index = interface_index("lo").unwrap();
+        let conf = ResolvConf::from_str("nameserver fe80::1%lo").unwrap();
+        assert!(matches!(conf.nameservers[0], SocketAddr::V6(a) if a.scope_id() == index));
+
+        assert!(interface_index("../lo").is_none());
+    }
+
+    #[test]
+    fn from_file() {
+        let path = std::env::temp_dir().join(format!("dnslib-resolv-{}.conf", std::process::id()));
+        fs::write(&path, "nameserver 127.0.0.53\noptions edns0\n").unwrap();
+
+        let conf = ResolvConf::from_file(&path).unwrap();
+        assert_eq!(
+            conf.nameservers,
+            vec![SocketAddr::from(([127, 0, 0, 53], DNS_PORT))]
+        );
+        assert!(conf.edns0);
+
+        fs::remove_file(&path).unwrap();
+        assert!(ResolvConf::from_file(&path).is_err());
+    }
+}
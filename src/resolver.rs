@@ -0,0 +1,829 @@
+//! A stub resolver caching the responses of the name server for as long as their TTL allows.
+//!
+//! Negative answers (NXDOMAIN and NODATA) are cached too, for the time given by the SOA record
+//! of the authority section: https://datatracker.ietf.org/doc/html/rfc2308#section-5
+//! The number of responses cached is bounded, the ones expiring the soonest making room.
+//!
+//! Lookups follow the CNAME and DNAME records down to the records of the requested type.
+//!
+//! Relative names can be completed with a search list, as the C library does with the search and
+//! ndots settings of resolv.conf: https://man7.org/linux/man-pages/man5/resolv.conf.5.html
+//!
+//! A hosts table can be consulted before the name server for addresses and reverse names.
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use crate::client::DNSClient;
+use crate::error::{DNSError, DNSResult};
+use crate::hosts::Hosts;
+use crate::rfc1035::{
+    DNSQuery, DNSQuestion, DNSResourceRecord, DNSResponse, DomainName, PacketType, QClass, QType,
+    RdData, ResponseCode,
+};
+use crate::rfc6891::OPT;
+
+// Names are case insensitive, so the key uses the lowercase name
+type CacheKey = (String, QType, QClass);
+
+// Default upper bound of the responses kept in the cache
+const MAX_CACHE_ENTRIES: usize = 10_000;
+
+// Upper bound of CNAME and DNAME records followed for a lookup
+const MAX_CHAIN_LENGTH: usize = 16;
+
+// The result of a lookup
+#[derive(Debug)]
+pub struct Answer {
+    // CNAME and DNAME records leading from the name looked up to the final name
+    pub chain: Vec<DNSResourceRecord>,
+
+    // records of the requested type for the final name, empty for a negative answer
+    pub records: Vec<DNSResourceRecord>,
+
+    // the last response, with the final records or the negative answer
+    pub response: DNSResponse,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    response: DNSResponse,
+    inserted: Instant,
+    ttl: Duration,
+}
+
+impl CacheEntry {
+    fn expires(&self) -> Instant {
+        self.inserted + self.ttl
+    }
+    // The response with the TTLs reduced by the time spent in the cache, or None if expired
+    fn response(&self) -> Option<DNSResponse> {
+        let elapsed = self.inserted.elapsed();
+        if elapsed >= self.ttl {
+            return None;
+        }
+
+        let mut response = self.response.clone();
+        let elapsed = elapsed.as_secs() as u32;
+        for rr in response
+            .answer
+            .iter_mut()
+            .chain(response.authority.iter_mut())
+            .chain(response.additional.iter_mut())
+            .filter(|rr| rr.r#type != QType::OPT)
+        {
+            rr.ttl = rr.ttl.saturating_sub(elapsed);
+        }
+        Some(response)
+    }
+}
+
+#[derive(Debug)]
+pub struct Resolver {
+    client: DNSClient,
+    cache: Mutex<HashMap<CacheKey, CacheEntry>>,
+    max_entries: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+
+    // domains appended to relative names, and the number of dots from which a name is first
+    // tried as is
+    search: Vec<String>,
+    ndots: u8,
+
+    // static table consulted first
+    hosts: Option<Hosts>,
+}
+
+impl Resolver {
+    pub fn new(client: DNSClient) -> Self {
+        Self::with_search(client, Vec::new(), 1)
+    }
+
+    // Complete relative names with the search list, e.g. the one of resolv.conf
+    pub fn with_search(client: DNSClient, search: Vec<String>, ndots: u8) -> Self {
+        Self {
+            client,
+            cache: Mutex::new(HashMap::new()),
+            max_entries: MAX_CACHE_ENTRIES,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            search,
+            ndots,
+            hosts: None,
+        }
+    }
+
+    // Answer A, AAAA and PTR queries from the table when it has the name, e.g. /etc/hosts
+    pub fn with_hosts(mut self, hosts: Hosts) -> Self {
+        self.hosts = Some(hosts);
+        self
+    }
+
+    // Keep at most that many responses in the cache, 10000 by default
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    // The response from the cache if still valid, from the name server otherwise
+    pub fn resolve(&self, name: &str, qtype: QType, qclass: QClass) -> DNSResult<DNSResponse> {
+        let domain = DomainName::try_from(name)?;
+        if let Some(response) = self.hosts_response(&domain, qtype, qclass) {
+            debug!("{} {:?} found in the hosts table", domain, qtype);
+            return Ok(response);
+        }
+        let key = (domain.to_string().to_lowercase(), qtype, qclass);
+
+        if let Some(response) = self.cached(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(response);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let mut query = DNSQuery::default();
+        query.push_question(DNSQuestion::new(name, qtype, Some(qclass))?);
+        query.push_additional(DNSResourceRecord::from(OPT::default()));
+        let response = self.client.query(&query)?;
+
+        match cache_ttl(&response) {
+            Some(ttl) if !ttl.is_zero() => {
+                debug!("caching {:?} for {:?}", key, ttl);
+
+                // the SOA of a negative answer has the TTL of the answer itself
+                let mut cached = response.clone();
+                if cached.answer.is_empty() {
+                    for rr in cached.authority.iter_mut() {
+                        if rr.r#type == QType::SOA {
+                            rr.ttl = rr.ttl.min(ttl.as_secs() as u32);
+                        }
+                    }
+                }
+
+                self.store(
+                    key,
+                    CacheEntry {
+                        response: cached,
+                        inserted: Instant::now(),
+                        ttl,
+                    },
+                );
+            }
+            _ => debug!("not caching {:?}", key),
+        }
+
+        Ok(response)
+    }
+
+    // The records of the type for the name, following aliases: first within the response,
+    // then with a new query for the alias target
+    pub fn lookup(&self, name: &str, qtype: QType, qclass: QClass) -> DNSResult<Answer> {
+        let mut name = DomainName::try_from(name)?;
+        let mut chain = Vec::new();
+        let mut seen = HashSet::from([name.to_string().to_lowercase()]);
+
+        loop {
+            let response = self.resolve(&name.to_string(), qtype, qclass)?;
+
+            loop {
+                let records: Vec<_> = response
+                    .answer
+                    .iter()
+                    .filter(|rr| rr.name == name && rr.r#type == qtype && rr.class == qclass)
+                    .cloned()
+                    .collect();
+                if !records.is_empty() {
+                    return Ok(Answer {
+                        chain,
+                        records,
+                        response,
+                    });
+                }
+
+                // the name is an alias
+                let Some((link, target)) = alias(&response, &name)? else {
+                    return Ok(Answer {
+                        chain,
+                        records,
+                        response,
+                    });
+                };
+                debug!("{} is an alias for {}", name, target);
+
+                chain.push(link);
+                if chain.len() > MAX_CHAIN_LENGTH {
+                    return Err(DNSError::new(&format!(
+                        "more than {} aliases for {}",
+                        MAX_CHAIN_LENGTH, chain[0].name
+                    )));
+                }
+                if !seen.insert(target.to_string().to_lowercase()) {
+                    return Err(DNSError::new(&format!("alias loop on {}", target)));
+                }
+                name = target;
+
+                // the rest of the chain is not in the response: the RCODE and negative answer
+                // are those of the last name (RFC6604), otherwise the target is asked for
+                if !response.answer.iter().any(|rr| rr.name == name) {
+                    if response.response_code() != ResponseCode::NoError
+                        || response.authority.iter().any(|rr| rr.r#type == QType::SOA)
+                    {
+                        return Ok(Answer {
+                            chain,
+                            records: Vec::new(),
+                            response,
+                        });
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    // Look up the names built from the search list in turn, until one exists. The answer for
+    // the last one is returned if none exists. The hosts table is consulted for the name as
+    // given and for all candidates before any query is sent.
+    pub fn search(&self, name: &str, qtype: QType, qclass: QClass) -> DNSResult<Answer> {
+        let candidates = self.candidates(name);
+        let mut answer = None;
+
+        let hosted = std::iter::once(name)
+            .chain(candidates.iter().map(String::as_str))
+            .find(|candidate| self.in_hosts(candidate, qtype, qclass));
+        if let Some(hosted) = hosted {
+            debug!("{} found in the hosts table", hosted);
+            return self.lookup(hosted, qtype, qclass);
+        }
+
+        for candidate in &candidates {
+            debug!("trying {}", candidate);
+            let found = self.lookup(candidate, qtype, qclass)?;
+            if found.response.response_code() != ResponseCode::NXDomain {
+                return Ok(found);
+            }
+            answer = Some(found);
+        }
+
+        // there's always at least one candidate
+        Ok(answer.unwrap())
+    }
+
+    // The names to try for the name, in order. An absolute name (ending with a dot) is only
+    // tried as is. A name with at least ndots dots is tried as is first, others last.
+    pub fn candidates(&self, name: &str) -> Vec<String> {
+        if name.ends_with('.') {
+            return vec![name.to_string()];
+        }
+
+        let mut candidates: Vec<_> = self
+            .search
+            .iter()
+            .map(|domain| format!("{}.{}.", name, domain.trim_end_matches('.')))
+            .collect();
+        let absolute = format!("{}.", name);
+        if name.matches('.').count() >= self.ndots as usize {
+            candidates.insert(0, absolute);
+        } else {
+            candidates.push(absolute);
+        }
+        candidates
+    }
+
+    // Number of responses found in the cache
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    // Number of responses asked to the name server
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    // Forget all responses, expired or not
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    // Whether the hosts table has records of the type for the name
+    fn in_hosts(&self, name: &str, qtype: QType, qclass: QClass) -> bool {
+        DomainName::try_from(name)
+            .is_ok_and(|domain| self.hosts_response(&domain, qtype, qclass).is_some())
+    }
+
+    // A response made up of the hosts table records, as the name server would have sent it
+    fn hosts_response(
+        &self,
+        name: &DomainName,
+        qtype: QType,
+        qclass: QClass,
+    ) -> Option<DNSResponse> {
+        if qclass != QClass::IN {
+            return None;
+        }
+        let records = self.hosts.as_ref()?.lookup(name, qtype);
+        if records.is_empty() {
+            return None;
+        }
+
+        let mut response = DNSResponse::default();
+        response.header.flags.packet_type = PacketType::Response;
+        response.header.flags.authorative_answer = true;
+        response.push_question(DNSQuestion {
+            name: name.clone(),
+            r#type: qtype,
+            class: qclass,
+        });
+        for rr in records {
+            response.push_answer(rr);
+        }
+        Some(response)
+    }
+
+    // Add the response to the cache. When full, the expired responses are removed first, then
+    // those expiring the soonest.
+    fn store(&self, key: CacheKey, entry: CacheEntry) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap();
+
+        if cache.len() >= self.max_entries && !cache.contains_key(&key) {
+            let now = Instant::now();
+            cache.retain(|_, entry| entry.expires() > now);
+
+            while cache.len() >= self.max_entries {
+                let soonest = cache
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires())
+                    .map(|(key, _)| key.clone())
+                    .unwrap();
+                debug!("evicting {:?}", soonest);
+                cache.remove(&soonest);
+            }
+        }
+
+        cache.insert(key, entry);
+    }
+
+    fn cached(&self, key: &CacheKey) -> Option<DNSResponse> {
+        let mut cache = self.cache.lock().unwrap();
+        let response = cache.get(key)?.response();
+
+        if response.is_none() {
+            debug!("{:?} expired", key);
+            cache.remove(key);
+        }
+        response
+    }
+}
+
+// The CNAME or DNAME record making the name an alias, and the alias target. A DNAME is preferred
+// to the CNAME synthesized from it.
+fn alias(
+    response: &DNSResponse,
+    name: &DomainName,
+) -> DNSResult<Option<(DNSResourceRecord, DomainName)>> {
+    for rr in &response.answer {
+        if let Some(RdData::DNAME(target)) = &rr.rd_data {
+            if name.is_subdomain_of(&rr.name) && *name != rr.name {
+                return Ok(Some((rr.clone(), name.substitute(&rr.name, target)?)));
+            }
+        }
+    }
+
+    Ok(response.answer.iter().find_map(|rr| match &rr.rd_data {
+        Some(RdData::CNAME(target)) if rr.name == *name => Some((rr.clone(), target.clone())),
+        _ => None,
+    }))
+}
+
+// How long a response can be cached: the lowest TTL of the answer for a positive answer,
+// the lowest of the SOA TTL and SOA minimum for a negative one. Other responses (SERVFAIL,
+// REFUSED, no SOA for a negative answer...) are not cached.
+fn cache_ttl(response: &DNSResponse) -> Option<Duration> {
+    let ttl = match response.response_code() {
+        ResponseCode::NoError if !response.answer.is_empty() => {
+            response.answer.iter().map(|rr| rr.ttl).min()
+        }
+        ResponseCode::NoError | ResponseCode::NXDomain => {
+            response.authority.iter().find_map(|rr| match &rr.rd_data {
+                Some(RdData::SOA(soa)) => Some(rr.ttl.min(soa.minimum)),
+                _ => None,
+            })
+        }
+        _ => None,
+    }?;
+    Some(Duration::from_secs(ttl as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::UdpSocket;
+    use std::str::FromStr;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::client::ClientOptions;
+    use crate::network_order::{FromNetworkOrder, ToNetworkOrder};
+    use crate::rfc1035::{DNSMessage, SOA};
+
+    // stand-in server: www.example.com has an address with a 300s TTL, nothing else exists
+    // in example.com whose SOA minimum is 60s, except for aliases:
+    // - alias.example.com is a CNAME for www.example.com, given in the same response
+    // - far.example.com is a CNAME for www.example.com, given on its own
+    // - old.example.com is a DNAME for example.com
+    // - dangling.example.com is a CNAME for a name which doesn't exist
+    // - loop0.example.com and loop1.example.com are CNAMEs for each other
+    // - chainN.example.com is a CNAME for chainN+1.example.com
+    fn server(queries: Arc<AtomicUsize>) -> String {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let endpoint = udp.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            loop {
+                let (received, from) = udp.recv_from(&mut buffer).unwrap();
+                queries.fetch_add(1, Ordering::SeqCst);
+
+                let mut message = DNSMessage::default();
+                message
+                    .from_network_bytes(&mut Cursor::new(&buffer[..received]))
+                    .unwrap();
+                message.header.flags.packet_type = PacketType::Response;
+
+                let question = message.question[0].clone();
+                let www = DomainName::try_from("www.example.com").unwrap();
+                let address = rr(&www, QType::A, 300, RdData::A(0x7F000001));
+                let cname = |target: &str| {
+                    let target = DomainName::try_from(target).unwrap();
+                    rr(&question.name, QType::CNAME, 300, RdData::CNAME(target))
+                };
+                let label = question.name.labels[0].clone();
+
+                match question.name.to_string().as_str() {
+                    "www.example.com." => {
+                        if question.r#type == QType::A {
+                            message.push_answer(address);
+                        }
+                    }
+                    "alias.example.com." => {
+                        message.push_answer(cname("www.example.com"));
+                        message.push_answer(address);
+                    }
+                    "far.example.com." => message.push_answer(cname("www.example.com")),
+                    "www.old.example.com." => {
+                        let old = DomainName::try_from("old.example.com").unwrap();
+                        let target = DomainName::try_from("example.com").unwrap();
+                        message.push_answer(rr(&old, QType::DNAME, 300, RdData::DNAME(target)));
+                        message.push_answer(cname("www.example.com"));
+                        message.push_answer(address);
+                    }
+                    "dangling.example.com." => {
+                        message.push_answer(cname("nowhere.example.com"));
+                        message.set_response_code(ResponseCode::NXDomain);
+                    }
+                    _ if label.starts_with("loop") => {
+                        let n = label.trim_start_matches("loop").parse::<u8>().unwrap();
+                        message.push_answer(cname(&format!("loop{}.example.com", 1 - n)));
+                    }
+                    _ if label.starts_with("chain") => {
+                        let n = label.trim_start_matches("chain").parse::<u8>().unwrap();
+                        message.push_answer(cname(&format!("chain{}.example.com", n + 1)));
+                    }
+                    _ => message.set_response_code(ResponseCode::NXDomain),
+                }
+                if message.answer.is_empty() {
+                    let soa = SOA {
+                        mname: DomainName::try_from("ns.example.com").unwrap(),
+                        rname: DomainName::try_from("admin.example.com").unwrap(),
+                        serial: 1,
+                        refresh: 3600,
+                        retry: 600,
+                        expire: 86400,
+                        minimum: 60,
+                    };
+                    let zone = DomainName::try_from("example.com").unwrap();
+                    message.push_authority(rr(&zone, QType::SOA, 3600, RdData::SOA(soa)));
+                }
+
+                let mut response = Vec::new();
+                message.to_network_bytes(&mut response).unwrap();
+                udp.send_to(&response, from).unwrap();
+            }
+        });
+
+        endpoint.to_string()
+    }
+
+    fn rr(name: &DomainName, r#type: QType, ttl: u32, rd_data: RdData) -> DNSResourceRecord {
+        DNSResourceRecord {
+            name: name.clone(),
+            r#type,
+            class: QClass::IN,
+            ttl,
+            rd_length: 0,
+            rd_data: Some(rd_data),
+        }
+    }
+
+    // pretend the entry was cached earlier
+    fn age(resolver: &Resolver, name: &str, qtype: QType, by: Duration) {
+        let key = (name.to_string(), qtype, QClass::IN);
+        resolver
+            .cache
+            .lock()
+            .unwrap()
+            .get_mut(&key)
+            .unwrap()
+            .inserted -= by;
+    }
+
+    #[test]
+    fn positive_cache() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let client = DNSClient::new(&server(queries.clone()), ClientOptions::default()).unwrap();
+        let resolver = Resolver::new(client);
+
+        let response = resolver
+            .resolve("www.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert_eq!(response.answer[0].ttl, 300);
+        assert_eq!((resolver.hits(), resolver.misses()), (0, 1));
+
+        // the TTL is decremented while in the cache, and names are case insensitive
+        age(
+            &resolver,
+            "www.example.com.",
+            QType::A,
+            Duration::from_secs(100),
+        );
+        let response = resolver
+            .resolve("WWW.Example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert_eq!(response.answer[0].ttl, 200);
+        assert_eq!((resolver.hits(), resolver.misses()), (1, 1));
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+        // expired
+        age(
+            &resolver,
+            "www.example.com.",
+            QType::A,
+            Duration::from_secs(200),
+        );
+        let response = resolver
+            .resolve("www.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert_eq!(response.answer[0].ttl, 300);
+        assert_eq!((resolver.hits(), resolver.misses()), (1, 2));
+        assert_eq!(queries.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn negative_cache() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let client = DNSClient::new(&server(queries.clone()), ClientOptions::default()).unwrap();
+        let resolver = Resolver::new(client);
+
+        // NXDOMAIN and NODATA are cached for the SOA minimum
+        for (name, qtype, rcode) in [
+            ("nowhere.example.com", QType::A, ResponseCode::NXDomain),
+            ("www.example.com", QType::AAAA, ResponseCode::NoError),
+        ] {
+            let response = resolver.resolve(name, qtype, QClass::IN).unwrap();
+            assert_eq!(response.response_code(), rcode);
+            assert!(response.answer.is_empty());
+
+            age(
+                &resolver,
+                &format!("{}.", name),
+                qtype,
+                Duration::from_secs(59),
+            );
+            let response = resolver.resolve(name, qtype, QClass::IN).unwrap();
+            assert_eq!(response.response_code(), rcode);
+            assert_eq!(response.authority[0].ttl, 1);
+
+            age(
+                &resolver,
+                &format!("{}.", name),
+                qtype,
+                Duration::from_secs(1),
+            );
+            resolver.resolve(name, qtype, QClass::IN).unwrap();
+        }
+        assert_eq!((resolver.hits(), resolver.misses()), (2, 4));
+        assert_eq!(queries.load(Ordering::SeqCst), 4);
+
+        resolver.clear();
+        resolver
+            .resolve("www.example.com", QType::AAAA, QClass::IN)
+            .unwrap();
+        assert_eq!(queries.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn cache_limit() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let client = DNSClient::new(&server(queries.clone()), ClientOptions::default()).unwrap();
+        let resolver = Resolver::new(client).with_max_entries(2);
+        let cached = |name: &str| {
+            let key = (name.to_string(), QType::A, QClass::IN);
+            resolver.cache.lock().unwrap().contains_key(&key)
+        };
+
+        // full: the response expiring the soonest makes room
+        for name in [
+            "www.example.com",
+            "nowhere1.example.com",
+            "nowhere2.example.com",
+        ] {
+            resolver.resolve(name, QType::A, QClass::IN).unwrap();
+        }
+        assert_eq!(resolver.cache.lock().unwrap().len(), 2);
+        assert!(cached("www.example.com."));
+        assert!(!cached("nowhere1.example.com."));
+        assert!(cached("nowhere2.example.com."));
+
+        // full: expired responses are removed first
+        age(
+            &resolver,
+            "www.example.com.",
+            QType::A,
+            Duration::from_secs(300),
+        );
+        resolver
+            .resolve("nowhere3.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert_eq!(resolver.cache.lock().unwrap().len(), 2);
+        assert!(!cached("www.example.com."));
+        assert!(cached("nowhere2.example.com."));
+        assert!(cached("nowhere3.example.com."));
+    }
+
+    #[test]
+    fn alias_chains() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let client = DNSClient::new(&server(queries.clone()), ClientOptions::default()).unwrap();
+        let resolver = Resolver::new(client);
+        let www = DomainName::try_from("www.example.com").unwrap();
+
+        // no alias
+        let answer = resolver
+            .lookup("www.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert!(answer.chain.is_empty());
+        assert_eq!(answer.records.len(), 1);
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+        // the whole chain is in the response
+        let answer = resolver
+            .lookup("alias.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert_eq!(answer.chain.len(), 1);
+        assert_eq!(answer.records[0].name, www);
+        assert!(matches!(
+            answer.records[0].rd_data,
+            Some(RdData::A(0x7F000001))
+        ));
+        assert_eq!(queries.load(Ordering::SeqCst), 2);
+
+        // the target is asked for, and found in the cache
+        let answer = resolver
+            .lookup("far.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert!(matches!(&answer.chain[0].rd_data, Some(RdData::CNAME(target)) if *target == www));
+        assert_eq!(answer.records[0].name, www);
+        assert_eq!(queries.load(Ordering::SeqCst), 3);
+        assert_eq!(resolver.hits(), 1);
+
+        // the DNAME is followed rather than the synthesized CNAME
+        let answer = resolver
+            .lookup("www.old.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert_eq!(answer.chain.len(), 1);
+        assert_eq!(answer.chain[0].r#type, QType::DNAME);
+        assert_eq!(answer.records[0].name, www);
+
+        // the target doesn't exist: no need to ask for it
+        let answer = resolver
+            .lookup("dangling.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert_eq!(answer.chain.len(), 1);
+        assert!(answer.records.is_empty());
+        assert_eq!(answer.response.response_code(), ResponseCode::NXDomain);
+        assert_eq!(queries.load(Ordering::SeqCst), 5);
+
+        // loops and long chains
+        assert!(resolver
+            .lookup("loop0.example.com", QType::A, QClass::IN)
+            .is_err());
+        assert!(resolver
+            .lookup("chain0.example.com", QType::A, QClass::IN)
+            .is_err());
+        assert_eq!(queries.load(Ordering::SeqCst), 5 + 2 + MAX_CHAIN_LENGTH + 1);
+    }
+
+    #[test]
+    fn search_list() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let client = DNSClient::new(&server(queries.clone()), ClientOptions::default()).unwrap();
+        let search = vec![String::from("example.net"), String::from("example.com.")];
+        let resolver = Resolver::with_search(client, search, 2);
+
+        assert_eq!(
+            resolver.candidates("www"),
+            vec!["www.example.net.", "www.example.com.", "www."]
+        );
+        assert_eq!(
+            resolver.candidates("a.b.c"),
+            vec!["a.b.c.", "a.b.c.example.net.", "a.b.c.example.com."]
+        );
+        assert_eq!(
+            resolver.candidates("www.example.org."),
+            vec!["www.example.org."]
+        );
+
+        // www.example.net doesn't exist, www.example.com does
+        let answer = resolver.search("www", QType::A, QClass::IN).unwrap();
+        assert_eq!(answer.records[0].name.to_string(), "www.example.com.");
+        assert_eq!(queries.load(Ordering::SeqCst), 2);
+
+        // NODATA stops the search
+        let answer = resolver.search("www", QType::AAAA, QClass::IN).unwrap();
+        assert!(answer.records.is_empty());
+        assert_eq!(answer.response.response_code(), ResponseCode::NoError);
+        assert_eq!(queries.load(Ordering::SeqCst), 4);
+
+        // nothing exists: the last answer is returned
+        let answer = resolver.search("nowhere", QType::A, QClass::IN).unwrap();
+        assert_eq!(answer.response.response_code(), ResponseCode::NXDomain);
+        assert_eq!(answer.response.question[0].name.to_string(), "nowhere.");
+        assert_eq!(queries.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn hosts_table() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let client = DNSClient::new(&server(queries.clone()), ClientOptions::default()).unwrap();
+        let hosts = Hosts::from_str("192.0.2.1 www.example.com\n192.0.2.2 printer.lan").unwrap();
+        let resolver =
+            Resolver::with_search(client, vec![String::from("lan")], 1).with_hosts(hosts);
+
+        // the table overrides the name server
+        let answer = resolver
+            .lookup("www.example.com", QType::A, QClass::IN)
+            .unwrap();
+        assert!(matches!(
+            answer.records[0].rd_data,
+            Some(RdData::A(0xC0000201))
+        ));
+        assert_eq!(
+            answer.response.question[0].name.to_string(),
+            "www.example.com."
+        );
+
+        let answer = resolver.search("printer", QType::A, QClass::IN).unwrap();
+        assert_eq!(answer.records[0].name.to_string(), "printer.lan.");
+
+        let answer = resolver
+            .lookup("2.2.0.192.in-addr.arpa", QType::PTR, QClass::IN)
+            .unwrap();
+        assert!(
+            matches!(&answer.records[0].rd_data, Some(RdData::PTR(name)) if name.to_string() == "printer.lan.")
+        );
+        assert_eq!(queries.load(Ordering::SeqCst), 0);
+        assert_eq!((resolver.hits(), resolver.misses()), (0, 0));
+
+        // other types and names are asked for
+        let answer = resolver
+            .lookup("www.example.com", QType::AAAA, QClass::IN)
+            .unwrap();
+        assert!(answer.records.is_empty());
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn hosts_table_before_search_list() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let client = DNSClient::new(&server(queries.clone()), ClientOptions::default()).unwrap();
+        let hosts = Hosts::from_str("192.0.2.3 www").unwrap();
+        let resolver =
+            Resolver::with_search(client, vec![String::from("example.com")], 1).with_hosts(hosts);
+
+        // www.example.com exists on the name server, but the bare entry wins without a query
+        let answer = resolver.search("www", QType::A, QClass::IN).unwrap();
+        assert!(matches!(
+            answer.records[0].rd_data,
+            Some(RdData::A(0xC0000203))
+        ));
+        assert_eq!(answer.records[0].name.to_string(), "www.");
+        assert_eq!(queries.load(Ordering::SeqCst), 0);
+    }
+}
@@ -6,11 +6,9 @@
 //! FIXME:  clean-up errors
 //!         check DnsEnum macro
 //! TODO:   start integration tests
-//!         move DnsResponse to response.rs
 use std::fmt;
 use std::fmt::Debug;
-use std::io::Cursor;
-use std::net::UdpSocket;
+use std::io::{Cursor, Read};
 use std::str;
 
 use log::debug;
@@ -18,25 +16,45 @@ use rand::Rng;
 
 use crate::error::{DNSError, DNSResult, InternalError};
 use crate::format_buffer;
-use crate::network_order::{FromNetworkOrder, ToNetworkOrder};
+use crate::network_order::{
+    compression::CompressionTable, read_slice, FromNetworkOrder, ToNetworkOrder,
+};
+use crate::rfc6891::{Cookie, EdnsOption, ExtendedError, OptionCode, Padding, OPT};
+use crate::transport::Transport;
 use crate::util::is_pointer;
 
 use dns_derive::{DnsEnum, DnsFromNetwork, DnsToNetwork};
 
-// DNS ports
-const UDP_PORT: u16 = 53;
-
 // DNS packets are called "messages" in RFC1035:
 // "All communications inside of the domain protocol are carried in a single format called a message"
-// We can break it down as QUERY and RESPONSE
-#[derive(Debug, DnsToNetwork)]
-pub struct DNSQuery {
+//
+//     +---------------------+
+//     |        Header       |
+//     +---------------------+
+//     |       Question      | the question for the name server
+//     +---------------------+
+//     |        Answer       | RRs answering the question
+//     +---------------------+
+//     |      Authority      | RRs pointing toward an authority
+//     +---------------------+
+//     |      Additional     | RRs holding additional information
+//     +---------------------+
+//
+// The same structure is used for both queries and responses.
+#[derive(Debug, Clone)]
+pub struct DNSMessage {
     pub header: DNSPacketHeader,
     pub question: Vec<DNSQuestion>,
-    pub additional: Option<Vec<Box<dyn ToNetworkOrder>>>,
+    pub answer: Vec<DNSResourceRecord>,
+    pub authority: Vec<DNSResourceRecord>,
+    pub additional: Vec<DNSResourceRecord>,
 }
 
-impl DNSQuery {
+// A query or a response is just a message whose header tells what it is
+pub type DNSQuery = DNSMessage;
+pub type DNSResponse = DNSMessage;
+
+impl DNSMessage {
     // Add another question into the list of questions to send
     pub fn push_question(&mut self, question: DNSQuestion) {
         self.question.push(question);
@@ -45,8 +63,26 @@ impl DNSQuery {
         self.header.qd_count += 1;
     }
 
-    // Send the query through the wire
-    pub fn send(&self, socket: &UdpSocket, endpoint: &str) -> DNSResult<()> {
+    // Add a RR into the answer section
+    pub fn push_answer(&mut self, rr: DNSResourceRecord) {
+        self.answer.push(rr);
+        self.header.an_count += 1;
+    }
+
+    // Add a RR into the authority section
+    pub fn push_authority(&mut self, rr: DNSResourceRecord) {
+        self.authority.push(rr);
+        self.header.ns_count += 1;
+    }
+
+    // Add a RR into the additional section, like the OPT pseudo-RR
+    pub fn push_additional(&mut self, rr: DNSResourceRecord) {
+        self.additional.push(rr);
+        self.header.ar_count += 1;
+    }
+
+    // Send the message through the wire
+    pub fn send<T: Transport + ?Sized>(&self, transport: &mut T) -> DNSResult<usize> {
         // convert to network bytes
         let mut buffer: Vec<u8> = Vec::new();
         self.to_network_bytes(&mut buffer)?;
@@ -54,15 +90,136 @@ impl DNSQuery {
         debug!("query buffer: [{}", format_buffer!("C", &buffer));
 
         // send packet through the wire
-        let dest = format!("{}:53", endpoint);
-        debug!("destination: {}", dest);
-        socket.send_to(&buffer, dest)?;
+        transport.send(&buffer)
+    }
 
-        Ok(())
+    // Receive a message from the wire
+    pub fn receive<T: Transport + ?Sized>(transport: &mut T) -> DNSResult<DNSMessage> {
+        let buffer = transport.recv()?;
+        debug!("received buffer: {}", format_buffer!("X", &buffer));
+        debug!("received buffer: [{}", format_buffer!("C", &buffer));
+
+        let mut message = DNSMessage::default();
+        message.from_network_bytes(&mut Cursor::new(buffer.as_slice()))?;
+        Ok(message)
+    }
+
+    // The OPT pseudo-RR of the additional section, if any
+    pub fn opt(&self) -> Option<&OPT> {
+        self.additional.iter().find_map(|rr| match &rr.rd_data {
+            Some(RdData::OPT(opt)) => Some(opt),
+            _ => None,
+        })
+    }
+
+    pub fn opt_mut(&mut self) -> Option<&mut OPT> {
+        self.additional
+            .iter_mut()
+            .find_map(|rr| match &mut rr.rd_data {
+                Some(RdData::OPT(opt)) => Some(opt),
+                _ => None,
+            })
+    }
+
+    // True if this message is the response to the query: it must carry the same id and
+    // echo the question (RFC5452). Some servers don't echo the question on errors.
+    // When both carry a cookie, the client cookie must be echoed as well (RFC7873).
+    pub fn is_response_to(&self, query: &DNSMessage) -> bool {
+        self.header.flags.packet_type == PacketType::Response
+            && self.header.id == query.header.id
+            && (self.question.is_empty() || self.question == query.question)
+            && match (query.cookie(), self.cookie()) {
+                (Some(sent), Some(received)) => sent.client == received.client,
+                _ => true,
+            }
+    }
+
+    /// The 12-bit RCODE, made of the 4 bits of the header and the 8 bits of the OPT
+    /// extended RCODE (RFC6891)
+    ///
+    /// ```
+    /// use dnslib::rfc1035::{DNSMessage, DNSResourceRecord, ResponseCode};
+    /// use dnslib::rfc6891::OPT;
+    ///
+    /// let mut message = DNSMessage::default();
+    /// message.header.flags.response_code = ResponseCode::NXDomain;
+    /// assert_eq!(message.response_code(), ResponseCode::NXDomain);
+    ///
+    /// // BADCOOKIE (23) is 7 in the header and 1 in the OPT record
+    /// message.push_additional(DNSResourceRecord::from(OPT::default()));
+    /// message.set_response_code(ResponseCode::BADCOOKIE);
+    /// assert_eq!(message.header.flags.response_code, ResponseCode::YXRRSet);
+    /// assert_eq!(message.opt().unwrap().ttl.extended_rcode, 1);
+    /// assert_eq!(message.response_code(), ResponseCode::BADCOOKIE);
+    /// ```
+    pub fn response_code(&self) -> ResponseCode {
+        let upper = self.opt().map_or(0, |opt| opt.ttl.extended_rcode as u16);
+        let lower = u16::from(self.header.flags.response_code) & 0b1111;
+
+        // all values are valid thanks to the Unknown variant
+        ResponseCode::try_from(upper << 4 | lower).unwrap()
+    }
+
+    // Split the RCODE between the header and the OPT record. Without OPT, only the lower 4
+    // bits are kept.
+    pub fn set_response_code(&mut self, response_code: ResponseCode) {
+        let value = u16::from(response_code);
+
+        self.header.flags.response_code = ResponseCode::try_from(value & 0b1111).unwrap();
+        if let Some(opt) = self.opt_mut() {
+            opt.ttl.extended_rcode = (value >> 4) as u8;
+        }
+    }
+
+    // The EDE options of the OPT pseudo-RR, explaining the RCODE (RFC8914)
+    pub fn extended_errors(&self) -> Vec<&ExtendedError> {
+        self.opt()
+            .map(|opt| {
+                opt.options
+                    .iter()
+                    .filter_map(|o| match o {
+                        EdnsOption::ExtendedError(ede) => Some(ede),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // The COOKIE option of the OPT pseudo-RR, if any
+    pub fn cookie(&self) -> Option<&Cookie> {
+        match self.opt()?.option(OptionCode::COOKIE) {
+            Some(EdnsOption::Cookie(cookie)) => Some(cookie),
+            _ => None,
+        }
+    }
+
+    // Read count resource records from the buffer. The count comes from the header and can't be
+    // trusted: no more records than the bytes left can hold are allocated beforehand.
+    fn resource_records(
+        buffer: &mut Cursor<&[u8]>,
+        count: u16,
+    ) -> DNSResult<Vec<DNSResourceRecord>> {
+        let left = buffer
+            .get_ref()
+            .len()
+            .saturating_sub(buffer.position() as usize);
+        let mut rrs = Vec::with_capacity((count as usize).min(left / MIN_RR_LENGTH));
+
+        for i in 0..count {
+            debug!("i={}", i);
+            let mut rr = DNSResourceRecord::default();
+            rr.from_network_bytes(buffer)?;
+            debug!("after rr.from_network_bytes()");
+
+            rrs.push(rr);
+        }
+
+        Ok(rrs)
     }
 }
 
-impl Default for DNSQuery {
+impl Default for DNSMessage {
     fn default() -> Self {
         let mut header = DNSPacketHeader::default();
 
@@ -76,58 +233,112 @@ impl Default for DNSQuery {
 
         // all others fields are either 0 or false
         Self {
-            header: header,
+            header,
             question: Vec::new(),
-            additional: None,
+            answer: Vec::new(),
+            authority: Vec::new(),
+            additional: Vec::new(),
         }
     }
 }
 
-#[derive(Debug, Default)]
-pub struct DNSResponse {
-    pub header: DNSPacketHeader,
-    pub question: DNSQuestion,
-    pub answer: Vec<DNSResourceRecord>,
-    pub authority: Option<Vec<DNSResourceRecord>>,
-    pub additional: Option<Vec<DNSResourceRecord>>,
+impl ToNetworkOrder for DNSMessage {
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> std::io::Result<usize> {
+        // domain names are compressed whenever possible: the message starts here
+        let mut table = CompressionTable::new(buffer.len());
+        self.to_network_bytes_compressed(buffer, &mut table)
+    }
+
+    // With a padding block length, the message is first written without padding to get
+    // the padding length (RFC8467)
+    fn to_network_bytes_compressed(
+        &self,
+        buffer: &mut Vec<u8>,
+        table: &mut CompressionTable,
+    ) -> std::io::Result<usize> {
+        let block_padding = matches!(
+            self.opt().and_then(|opt| opt.option(OptionCode::PADDING)),
+            Some(EdnsOption::Padding(Padding {
+                block_length: Some(_),
+                ..
+            }))
+        );
+        if !block_padding {
+            return self.write(buffer, table);
+        }
+
+        let mut message = self.clone();
+        let padding = message.padding_mut().unwrap();
+        padding.length = 0;
+
+        let mut unpadded = Vec::new();
+        let length = message.write(&mut unpadded, &mut CompressionTable::new(0))?;
+
+        let padding = message.padding_mut().unwrap();
+        padding.length = padding.padding_length(length);
+        message.write(buffer, table)
+    }
 }
 
-impl<'a> FromNetworkOrder<'a> for DNSResponse {
+impl DNSMessage {
+    fn padding_mut(&mut self) -> Option<&mut Padding> {
+        self.opt_mut().and_then(|opt| opt.padding_mut())
+    }
+
+    // Write all sections, domain names being compressed
+    fn write(&self, buffer: &mut Vec<u8>, table: &mut CompressionTable) -> std::io::Result<usize> {
+        let mut length = self.header.to_network_bytes(buffer)?;
+        length += self.question.to_network_bytes_compressed(buffer, table)?;
+        length += self.answer.to_network_bytes_compressed(buffer, table)?;
+        length += self.authority.to_network_bytes_compressed(buffer, table)?;
+        length += self.additional.to_network_bytes_compressed(buffer, table)?;
+        Ok(length)
+    }
+}
+
+impl<'a> FromNetworkOrder<'a> for DNSMessage {
     fn from_network_bytes(&mut self, buffer: &mut Cursor<&'a [u8]>) -> DNSResult<()> {
         self.header.from_network_bytes(buffer)?;
         debug!(
-            "DNSResponse::from_network_bytes() header: {:#?}",
+            "DNSMessage::from_network_bytes() header: {:#?}",
             self.header
         );
 
         debug!("{:02X?}", buffer);
         debug!("position={}", buffer.position());
 
-        debug!("before question");
-        self.question.from_network_bytes(buffer)?;
-        debug!("after question");
+        // handle questions, usually only one
+        debug!("self.header.qd_count={}", self.header.qd_count);
+        self.question.clear();
+        for _ in 0..self.header.qd_count {
+            let mut question = DNSQuestion::default();
+            question.from_network_bytes(buffer)?;
+            self.question.push(question);
+        }
 
         // handle answers
         debug!("self.header.an_count={}", self.header.an_count);
-        //0panic!();
-        for i in 0..self.header.an_count {
-            debug!("i={}", i);
-            let mut rr = DNSResourceRecord::default();
-            rr.from_network_bytes(buffer)?;
-            debug!("after rr.from_network_bytes()");
+        self.answer = DNSMessage::resource_records(buffer, self.header.an_count)?;
 
-            self.answer.push(rr);
-        }
+        // authority section: NS referrals or SOA for negative answers
+        debug!("self.header.ns_count={}", self.header.ns_count);
+        self.authority = DNSMessage::resource_records(buffer, self.header.ns_count)?;
+
+        // additional section: glue records and the OPT pseudo-RR
+        debug!("self.header.ar_count={}", self.header.ar_count);
+        self.additional = DNSMessage::resource_records(buffer, self.header.ar_count)?;
 
-        // if a pointer, get pointer value and call
         Ok(())
     }
 }
 
 pub const MAX_DNS_PACKET_SIZE: usize = 512;
 
+// Smallest resource record on the wire: root name, type, class, TTL and an empty RDATA
+const MIN_RR_LENGTH: usize = 11;
+
 // DNS packet header: https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.1
-#[derive(Debug, Default, DnsToNetwork, DnsFromNetwork)]
+#[derive(Debug, Default, Clone, DnsToNetwork, DnsFromNetwork)]
 pub struct DNSPacketHeader {
     pub id: u16, // A 16 bit identifier assigned by the program that
     //   generates any kind of query.  This identifier is copied
@@ -145,7 +356,7 @@ pub struct DNSPacketHeader {
 }
 
 // Flags: https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.1
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct DNSPacketFlags {
     pub packet_type: PacketType, // A one bit field that specifies whether this message is a query (0), or a response (1).
     pub op_code: OpCode,         // A four bit field that specifies kind of query in this
@@ -237,6 +448,9 @@ pub enum OpCode {
 }
 
 // response codes: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-6
+//
+// The header only carries the lower 4 bits, the upper 8 bits being in the OPT record. Use
+// DNSMessage::response_code() to get the full 12-bit code.
 #[derive(Debug, Clone, Copy, PartialEq, DnsEnum)]
 #[repr(u16)]
 pub enum ResponseCode {
@@ -263,6 +477,7 @@ pub enum ResponseCode {
     BADALG = 21,    // Algorithm not supported	[RFC2930]
     BADTRUNC = 22,  // 	Bad Truncation	[RFC8945]
     BADCOOKIE = 23, //	Bad/missing Server Cookie	[RFC7873]
+    Unknown(u16),   // unassigned or private use
 }
 
 // // RR format
@@ -298,7 +513,22 @@ pub enum ResponseCode {
 // }
 
 // RR type codes: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-4
-#[derive(Debug, Copy, Clone, PartialEq, DnsEnum)]
+/// ```
+/// use std::str::FromStr;
+/// use dnslib::rfc1035::QType;
+///
+/// assert_eq!(QType::try_from(15u16).unwrap(), QType::MX);
+/// assert_eq!(QType::try_from(65280u16).unwrap(), QType::Unknown(65280));
+/// assert_eq!(u16::from(QType::Unknown(65280)), 65280);
+/// assert_eq!(QType::from_str("TYPE65280").unwrap(), QType::Unknown(65280));
+/// assert_eq!(QType::from_str("TYPE15").unwrap(), QType::MX);
+/// assert!(QType::from_str("FOO").is_err());
+/// assert!(QType::from_str("FOO1").is_err());
+/// assert!(QType::from_str("CLASS1").is_err());
+/// assert!(QType::from_str("TYPE+1").is_err());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, DnsEnum)]
+#[dns(prefix = "TYPE")]
 #[repr(u16)]
 pub enum QType {
     A = 1,           // a host address	[RFC1035]
@@ -359,10 +589,10 @@ pub enum QType {
     NINFO = 56, // NINFO	[Jim_Reid]	NINFO/ninfo-completed-template	2008-01-21
     RKEY = 57, // RKEY	[Jim_Reid]	RKEY/rkey-completed-template	2008-01-21
     TALINK = 58, // Trust Anchor LINK	[Wouter_Wijngaards]	TALINK/talink-completed-template	2010-02-17
-    CDS = 59,  // Child DS	[RFC7344]	CDS/cds-completed-template	2011-06-06
+    CDS = 59,    // Child DS	[RFC7344]	CDS/cds-completed-template	2011-06-06
     CDNSKEY = 60, // DNSKEY(s) the Child wants reflected in DS	[RFC7344]		2014-06-16
     OPENPGPKEY = 61, // OpenPGP Key	[RFC7929]	OPENPGPKEY/openpgpkey-completed-template	2014-08-12
-    CSYNC = 62, // Child-To-Parent Synchronization	[RFC7477]		2015-01-27
+    CSYNC = 62,  // Child-To-Parent Synchronization	[RFC7477]		2015-01-27
     ZONEMD = 63, // Message Digest Over Zone Data	[RFC8976]	ZONEMD/zonemd-completed-template	2018-12-12
     SVCB = 64, // Service Binding	[draft-ietf-dnsop-svcb-https-00]	SVCB/svcb-completed-template	2020-06-30
     HTTPS = 65, // HTTPS Binding	[draft-ietf-dnsop-svcb-https-00]	HTTPS/https-completed-template	2020-06-30
@@ -394,10 +624,21 @@ pub enum QType {
     // Unassigned	261-32767
     TA = 32768, // DNSSEC Trust Authorities	[Sam_Weiler][http://cameo.library.cmu.edu/][ Deploying DNSSEC Without a Signed Root. Technical Report 1999-19, Information Networking Institute, Carnegie Mellon University, April 2004.]		2005-12-13
     DLV = 32769, // DNSSEC Lookaside Validation (OBSOLETE)	[RFC8749][RFC4431]
+    Unknown(u16), // any type code not listed above, handled as in RFC3597
 }
 
 // RR Class values: https://datatracker.ietf.org/doc/html/rfc1035#section-3.2.4
-#[derive(Debug, Copy, Clone, PartialEq, DnsEnum)]
+/// ```
+/// use std::str::FromStr;
+/// use dnslib::rfc1035::QClass;
+///
+/// assert_eq!(QClass::try_from(254u16).unwrap(), QClass::Unknown(254));
+/// assert_eq!(QClass::from_str("CLASS3").unwrap(), QClass::CH);
+/// assert_eq!(QClass::from_str("CLASS65280").unwrap(), QClass::Unknown(65280));
+/// assert!(QClass::from_str("TYPE1").is_err());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, DnsEnum)]
+#[dns(prefix = "CLASS")]
 #[repr(u16)]
 pub enum QClass {
     IN = 1, // the Internet
@@ -405,28 +646,43 @@ pub enum QClass {
     CH = 3, // the CHAOS class
     HS = 4, // Hesiod [Dyer 87]
     ANY = 255,
+    Unknown(u16), // any class not listed above, handled as in RFC3597
 }
 
 // Domain name: https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.4
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct DomainName {
     // a domain name is a list of labels as defined in the RFC1035
     pub labels: Vec<String>,
 }
 
+// Maximum length of a domain name and of a label as stated in RFC1035
+const MAX_DOMAIN_NAME_LENGTH: usize = 255;
+const MAX_LABEL_LENGTH: usize = 63;
+
 impl DomainName {
+    // Decode the domain name starting at pos in the whole message buffer, following compression
+    // pointers if any. Returns the position right after the domain name in the buffer.
     pub fn from_position(&mut self, pos: usize, buffer: &&[u8]) -> DNSResult<usize> {
         let mut index = pos;
 
-        println!(
-            "from_position(): starting at position: {} with value: {:X?} ({})",
-            index, buffer[index], buffer[index]
-        );
+        // start of the label sequence being read: a pointer must point before it, otherwise
+        // we would decode again the same labels, endlessly
+        let mut segment_start = pos;
+
+        // where the domain name ends in the buffer. It's after the first pointer if any
+        let mut end_position: Option<usize> = None;
+
+        // domain name length on the wire, including label length octets and the sentinel
+        let mut length = 1usize;
 
         loop {
+            let byte = *buffer.get(index).ok_or(DNSError::DNSInternalError(
+                InternalError::DnsOffsetOutOfRange,
+            ))?;
+
             // we reach the sentinel
-            if buffer[index] == 0 {
-                println!("from_position(): found sentinel");
+            if byte == 0 {
                 break;
             }
 
@@ -445,33 +701,56 @@ impl DomainName {
             //    the start of the message (i.e., the first octet of the ID field in the
             //    domain header).  A zero offset specifies the first byte of the ID field,
             //    etc.
-            //if buffer[index] >= 192 {
-            if is_pointer(buffer[index]) {
+            if is_pointer(byte) {
                 // get pointer which is on 2 bytes
-                let ptr = [buffer[index], buffer[index + 1]];
-                let pointer = u16::from_be_bytes(ptr);
-
-                // println!("pointer={:0b}", pointer);
-                // println!("pointer shifted={:0b}", (pointer << 2) >> 2);
-
-                let pointer = ((pointer << 2) >> 2) as usize;
-                //println!("pointer={:0b}", pointer);
+                let low = *buffer.get(index + 1).ok_or(DNSError::DNSInternalError(
+                    InternalError::DnsOffsetOutOfRange,
+                ))?;
+                let pointer = (((byte & 0b0011_1111) as usize) << 8) | low as usize;
+
+                // a pointer refers to a prior occurrence of a name
+                if pointer >= index {
+                    return Err(DNSError::DNSInternalError(InternalError::DnsForwardPointer));
+                }
+
+                // and never to the labels we're decoding
+                if pointer >= segment_start {
+                    return Err(DNSError::DNSInternalError(InternalError::DnsPointerLoop));
+                }
+
+                // only the first pointer tells where the domain name ends
+                if end_position.is_none() {
+                    end_position = Some(index + 2);
+                }
+
+                // continue with the pointed labels
+                index = pointer;
+                segment_start = pointer;
+                continue;
+            }
 
-                // recursively call the same method with the pointer as starting point
-                let _ = self.from_position(pointer as usize, buffer);
-                return Ok(index + 2);
+            // otherwise, regular processing: the first byte is the string length.
+            // 01 and 10 prefixes are reserved, so it's also a label too long
+            let size = byte as usize;
+            if size > MAX_LABEL_LENGTH {
+                return Err(DNSError::DNSInternalError(InternalError::DnsLabelTooLong));
             }
 
-            // otherwise, regular processing: the first byte is the string length
-            let size = buffer[index] as usize;
+            length += size + 1;
+            if length > MAX_DOMAIN_NAME_LENGTH {
+                return Err(DNSError::DNSInternalError(
+                    InternalError::DnsDomainNameTooLong,
+                ));
+            }
 
             // then we convert the label into UTF8
-            let label = &buffer[index + 1..index + size + 1];
+            let label =
+                buffer
+                    .get(index + 1..index + size + 1)
+                    .ok_or(DNSError::DNSInternalError(
+                        InternalError::DnsOffsetOutOfRange,
+                    ))?;
             let label_as_utf8 = String::from_utf8(label.to_vec())?;
-            println!(
-                "label_as_utf8={}, index={}, buffer[index]={:02X?}",
-                label_as_utf8, index, buffer[index]
-            );
 
             self.labels.push(label_as_utf8);
 
@@ -479,13 +758,7 @@ impl DomainName {
             index += size + 1;
         }
 
-        // println!(
-        //     "end index: {} with value: {:X?}",
-        //     index + 1,
-        //     buffer[index + 1]
-        // );
-
-        Ok(index + 1)
+        Ok(end_position.unwrap_or(index + 1))
     }
 }
 
@@ -512,6 +785,75 @@ impl fmt::Display for DomainName {
     }
 }
 
+/// Domain names are compared case-insensitively (RFC4343)
+///
+/// ```
+/// use dnslib::rfc1035::DomainName;
+///
+/// let dn = DomainName::try_from("www.example.com").unwrap();
+/// assert_eq!(dn, DomainName::try_from("WwW.ExAmPlE.cOm.").unwrap());
+/// assert_ne!(dn, DomainName::try_from("www.example.org").unwrap());
+/// assert_ne!(dn, DomainName::try_from("example.com").unwrap());
+/// ```
+impl PartialEq for DomainName {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(&other.labels)
+                .all(|(l1, l2)| l1.eq_ignore_ascii_case(l2))
+    }
+}
+
+/// ```
+/// use dnslib::rfc1035::DomainName;
+///
+/// let zone = DomainName::try_from("example.com").unwrap();
+/// assert!(DomainName::try_from("www.Example.com").unwrap().is_subdomain_of(&zone));
+/// assert!(zone.is_subdomain_of(&zone));
+/// assert!(zone.is_subdomain_of(&DomainName::try_from(".").unwrap()));
+/// assert!(!DomainName::try_from("www.example.org").unwrap().is_subdomain_of(&zone));
+/// assert!(!DomainName::try_from("myexample.com").unwrap().is_subdomain_of(&zone));
+///
+/// // DNAME substitution
+/// let owner = DomainName::try_from("example.com").unwrap();
+/// let target = DomainName::try_from("example.net").unwrap();
+/// let name = DomainName::try_from("www.example.com").unwrap();
+/// assert_eq!(name.substitute(&owner, &target).unwrap().to_string(), "www.example.net.");
+/// ```
+impl DomainName {
+    // True if the name is the zone itself or a name below it
+    pub fn is_subdomain_of(&self, zone: &DomainName) -> bool {
+        self.labels.len() >= zone.labels.len()
+            && self
+                .labels
+                .iter()
+                .rev()
+                .zip(zone.labels.iter().rev())
+                .all(|(l1, l2)| l1.eq_ignore_ascii_case(l2))
+    }
+
+    // Replace the owner suffix of the name by the target, as a DNAME does (RFC6672)
+    pub fn substitute(&self, owner: &DomainName, target: &DomainName) -> DNSResult<DomainName> {
+        if !self.is_subdomain_of(owner) {
+            return Err(DNSError::new(&format!("{} is not below {}", self, owner)));
+        }
+
+        let mut labels = self.labels[..self.labels.len() - owner.labels.len()].to_vec();
+        labels.extend_from_slice(&target.labels);
+
+        // each label is preceded by its length, and the root label ends the name
+        let length = labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1;
+        if length > MAX_DOMAIN_NAME_LENGTH {
+            return Err(DNSError::DNSInternalError(
+                InternalError::DnsDomainNameTooLong,
+            ));
+        }
+        Ok(DomainName { labels })
+    }
+}
+
 /// ```
 /// use dnslib::rfc1035::DomainName;
 ///
@@ -526,7 +868,7 @@ impl fmt::Display for DomainName {
 /// let dn = DomainName::try_from(".").unwrap();
 /// assert_eq!(dn.labels.len(), 0);
 /// assert!(dn.labels.is_empty());
-
+///
 /// assert!(DomainName::try_from("").is_err());
 /// ```
 impl TryFrom<&str> for DomainName {
@@ -554,7 +896,7 @@ impl TryFrom<&str> for DomainName {
 //--------------------------------------------------------------------------------
 // Question structure: https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.2
 //--------------------------------------------------------------------------------
-#[derive(Debug, Default, DnsToNetwork, DnsFromNetwork)]
+#[derive(Debug, Default, Clone, PartialEq, DnsToNetwork, DnsFromNetwork)]
 pub struct DNSQuestion {
     pub name: DomainName,
     pub r#type: QType,
@@ -580,7 +922,7 @@ impl DNSQuestion {
 //------------------------------------------------------------------------
 // Definition of a resource record in the RFC1035
 //------------------------------------------------------------------------
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct DNSResourceRecord {
     pub name: DomainName, // an owner name, i.e., the name of the node to which this resource record pertains.
     pub r#type: QType,    // two octets containing one of the RR TYPE codes.
@@ -600,11 +942,114 @@ pub struct DNSResourceRecord {
     //  according to the TYPE and CLASS of the resource record.
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RdData {
     A(A),
     AAAA(AAAA),
+    CNAME(CNAME),
+    DNAME(DNAME),
     HINFO(HINFO),
+    MX(MX),
+    NS(NS),
+    PTR(PTR),
+    SOA(SOA),
+    TXT(TXT),
+    OPT(OPT),
+    // RDATA for types we don't decode, kept as is (RFC3597)
+    Unknown { type_code: u16, bytes: Vec<u8> },
+}
+
+impl DNSResourceRecord {
+    // Write the RR to the buffer, with domain names compressed if a compression table is given
+    fn write(
+        &self,
+        buffer: &mut Vec<u8>,
+        mut table: Option<&mut CompressionTable>,
+    ) -> std::io::Result<usize> {
+        let mut length = match table.as_deref_mut() {
+            Some(t) => self.name.to_network_bytes_compressed(buffer, t)?,
+            None => self.name.to_network_bytes(buffer)?,
+        };
+        length += self.r#type.to_network_bytes(buffer)?;
+
+        // the OPT pseudo-RR uses CLASS and TTL for the UDP payload size, extended RCODE and flags
+        if let Some(RdData::OPT(opt)) = &self.rd_data {
+            length += opt.udp_payload_size.to_network_bytes(buffer)?;
+            length += opt.ttl.to_network_bytes(buffer)?;
+        } else {
+            length += self.class.to_network_bytes(buffer)?;
+            length += self.ttl.to_network_bytes(buffer)?;
+        }
+
+        // RDATA length is only known once written, because domain names might be compressed
+        let rd_length_position = buffer.len();
+        length += 0_u16.to_network_bytes(buffer)?;
+
+        let rd_length = match (&self.rd_data, table) {
+            (Some(rd_data), Some(t)) => rd_data.to_network_bytes_compressed(buffer, t)?,
+            (Some(rd_data), None) => rd_data.to_network_bytes(buffer)?,
+            (None, _) => 0,
+        };
+        let rd_length = u16::try_from(rd_length).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "RDATA too long for RDLENGTH",
+            )
+        })?;
+        buffer[rd_length_position..rd_length_position + 2]
+            .copy_from_slice(&rd_length.to_be_bytes());
+
+        Ok(length + rd_length as usize)
+    }
+}
+
+impl ToNetworkOrder for DNSResourceRecord {
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> std::io::Result<usize> {
+        self.write(buffer, None)
+    }
+
+    fn to_network_bytes_compressed(
+        &self,
+        buffer: &mut Vec<u8>,
+        table: &mut CompressionTable,
+    ) -> std::io::Result<usize> {
+        self.write(buffer, Some(table))
+    }
+}
+
+impl ToNetworkOrder for RdData {
+    fn to_network_bytes(&self, buffer: &mut Vec<u8>) -> std::io::Result<usize> {
+        match self {
+            RdData::A(a) => a.to_network_bytes(buffer),
+            RdData::AAAA(aaaa) => aaaa.to_network_bytes(buffer),
+            RdData::CNAME(cname) => cname.to_network_bytes(buffer),
+            RdData::DNAME(dname) => dname.to_network_bytes(buffer),
+            RdData::HINFO(hinfo) => hinfo.to_network_bytes(buffer),
+            RdData::MX(mx) => mx.to_network_bytes(buffer),
+            RdData::NS(ns) => ns.to_network_bytes(buffer),
+            RdData::PTR(ptr) => ptr.to_network_bytes(buffer),
+            RdData::SOA(soa) => soa.to_network_bytes(buffer),
+            RdData::TXT(txt) => txt.to_network_bytes(buffer),
+            RdData::OPT(opt) => opt.to_network_bytes(buffer),
+            RdData::Unknown { bytes, .. } => bytes.as_slice().to_network_bytes(buffer),
+        }
+    }
+
+    // only RFC1035 well-known types can be compressed (RFC3597)
+    fn to_network_bytes_compressed(
+        &self,
+        buffer: &mut Vec<u8>,
+        table: &mut CompressionTable,
+    ) -> std::io::Result<usize> {
+        match self {
+            RdData::CNAME(cname) => cname.to_network_bytes_compressed(buffer, table),
+            RdData::MX(mx) => mx.to_network_bytes_compressed(buffer, table),
+            RdData::NS(ns) => ns.to_network_bytes_compressed(buffer, table),
+            RdData::PTR(ptr) => ptr.to_network_bytes_compressed(buffer, table),
+            RdData::SOA(soa) => soa.to_network_bytes_compressed(buffer, table),
+            _ => self.to_network_bytes(buffer),
+        }
+    }
 }
 
 impl<'a> FromNetworkOrder<'a> for DNSResourceRecord {
@@ -612,10 +1057,29 @@ impl<'a> FromNetworkOrder<'a> for DNSResourceRecord {
         self.name.from_network_bytes(buffer)?;
         debug!("name={}", self.name);
         self.r#type.from_network_bytes(buffer)?;
+
+        // the OPT pseudo-RR doesn't follow the regular RR layout: CLASS is the requestor's
+        // UDP payload size and TTL holds the extended RCODE and flags (RFC6891)
+        if self.r#type == QType::OPT {
+            let mut opt = OPT::default();
+            opt.udp_payload_size.from_network_bytes(buffer)?;
+            opt.ttl.from_network_bytes(buffer)?;
+            self.rd_length.from_network_bytes(buffer)?;
+
+            // options are read from the RDATA only
+            let rdata = read_slice(buffer, self.rd_length)?;
+            opt.from_network_bytes(&mut Cursor::new(rdata))?;
+
+            self.rd_data = Some(RdData::OPT(opt));
+            return Ok(());
+        }
+
         self.class.from_network_bytes(buffer)?;
         self.ttl.from_network_bytes(buffer)?;
         self.rd_length.from_network_bytes(buffer)?;
 
+        let rd_end = buffer.position() + self.rd_length as u64;
+
         // depending on the QType, extract and keep values
         match self.r#type {
             QType::A => {
@@ -640,18 +1104,82 @@ impl<'a> FromNetworkOrder<'a> for DNSResourceRecord {
 
                 self.rd_data = Some(RdData::HINFO(hinfo));
             }
+            QType::CNAME => {
+                let mut cname = CNAME::default();
+                cname.from_network_bytes(buffer)?;
+
+                self.rd_data = Some(RdData::CNAME(cname));
+            }
+            QType::DNAME => {
+                let mut dname = DNAME::default();
+                dname.from_network_bytes(buffer)?;
+
+                self.rd_data = Some(RdData::DNAME(dname));
+            }
+            QType::NS => {
+                let mut ns = NS::default();
+                ns.from_network_bytes(buffer)?;
+
+                self.rd_data = Some(RdData::NS(ns));
+            }
+            QType::PTR => {
+                let mut ptr = PTR::default();
+                ptr.from_network_bytes(buffer)?;
+
+                self.rd_data = Some(RdData::PTR(ptr));
+            }
+            QType::MX => {
+                let mut mx = MX::default();
+                mx.from_network_bytes(buffer)?;
+
+                self.rd_data = Some(RdData::MX(mx));
+            }
+            QType::SOA => {
+                let mut soa = SOA::default();
+                soa.from_network_bytes(buffer)?;
+
+                self.rd_data = Some(RdData::SOA(soa));
+            }
+            QType::TXT => {
+                // TXT RDATA is one or more <character-string>s filling the whole RDATA: the last
+                // one can't go past it
+                let rdata = read_slice(buffer, self.rd_length)?;
+                let mut cursor = Cursor::new(rdata);
+                let mut txt = TXT::new();
+
+                while (cursor.position() as usize) < rdata.len() {
+                    let mut cs = CharacterString::default();
+                    cs.from_network_bytes(&mut cursor)?;
+                    txt.push(cs);
+                }
+
+                self.rd_data = Some(RdData::TXT(txt));
+            }
             _ => {
-                unimplemented!("type={:?} is not yet implemented !!", self.r#type)
+                // not decoded: keep the raw RDATA
+                let mut bytes = vec![0u8; self.rd_length as usize];
+                buffer.read_exact(&mut bytes)?;
+
+                self.rd_data = Some(RdData::Unknown {
+                    type_code: u16::from(self.r#type),
+                    bytes,
+                });
             }
         }
 
-        // if a pointer, get pointer value and call
+        // the RDATA decoded must span exactly rd_length bytes
+        if buffer.position() != rd_end {
+            return Err(DNSError::new(&format!(
+                "{:?} RDATA doesn't match RDLENGTH {}",
+                self.r#type, self.rd_length
+            )));
+        }
         Ok(())
     }
 }
 
 // Character string as described in: https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.4
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct CharacterString {
     pub length: u8,
     pub data: String,
@@ -682,7 +1210,7 @@ impl<'a> From<&'a str> for CharacterString {
 /// assert_eq!(cs.length, 3);
 /// assert_eq!(cs.to_string(), "www");
 /// ```
-impl<'a> fmt::Display for CharacterString {
+impl fmt::Display for CharacterString {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.data)
     }
@@ -696,7 +1224,7 @@ impl<'a> fmt::Display for CharacterString {
 pub type A = u32;
 
 // HINFO RR
-#[derive(Debug, Default, DnsFromNetwork)]
+#[derive(Debug, Default, Clone, DnsToNetwork, DnsFromNetwork)]
 pub struct HINFO {
     pub cpu: CharacterString,
     pub os: CharacterString,
@@ -705,6 +1233,9 @@ pub struct HINFO {
 // CNAME RR
 pub type CNAME = DomainName;
 
+// DNAME RR (RFC6672): its target is never compressed
+pub type DNAME = DomainName;
+
 // NS RR
 pub type NS = DomainName;
 
@@ -712,7 +1243,7 @@ pub type NS = DomainName;
 pub type AAAA = [u8; 16];
 
 // SOA RR
-#[derive(Debug, Default, DnsFromNetwork)]
+#[derive(Debug, Default, Clone, DnsToNetwork, DnsFromNetwork)]
 pub struct SOA {
     pub mname: DomainName, // The <domain-name> of the name server that was the
     // original or primary source of data for this zone.
@@ -737,7 +1268,7 @@ pub struct SOA {
 pub type PTR = DomainName;
 
 // MX RR
-#[derive(Debug, Default, DnsFromNetwork)]
+#[derive(Debug, Default, Clone, DnsToNetwork, DnsFromNetwork)]
 pub struct MX {
     pub preference: u16, // A 16 bit integer which specifies the preference given to
     // this RR among others at the same owner.  Lower values
@@ -746,100 +1277,21 @@ pub struct MX {
 }
 
 // TXT RR
-pub type TXT = String;
+pub type TXT = Vec<CharacterString>;
 
 // RDATA RR
 pub type RDATA = u32;
 
-// OPT RR: https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.2
-// RR format
-// +------------+--------------+------------------------------+
-// | Field Name | Field Type   | Description                  |
-// +------------+--------------+------------------------------+
-// | NAME       | domain name  | MUST be 0 (root domain)      |
-// | TYPE       | u_int16_t    | OPT (41)                     |
-// | CLASS      | u_int16_t    | requestor's UDP payload size |
-// | TTL        | u_int32_t    | extended RCODE and flags     |
-// | RDLEN      | u_int16_t    | length of all RDATA          |
-// | RDATA      | octet stream | {attribute,value} pairs      |
-// +------------+--------------+------------------------------+
-#[derive(Debug, DnsToNetwork)]
-pub struct OPT {
-    pub name: u8,                                      // MUST be 0 (root domain)
-    pub r#type: QType,                                 // OPT (41)
-    pub udp_payload_size: u16,                         // requestor's UDP payload size
-    pub ttl: OptTTL,                                   // extended RCODE and flags
-    pub rd_length: u16,                                // length of all RDATA
-    pub rd_data: Option<Vec<Box<dyn ToNetworkOrder>>>, // {attribute,value} pairs (OptData struct)
-}
-
-impl Default for OPT {
-    fn default() -> Self {
-        Self {
-            name: 0,
+// The OPT pseudo-RR is carried in the additional section like any other RR
+impl From<OPT> for DNSResourceRecord {
+    fn from(opt: OPT) -> Self {
+        DNSResourceRecord {
+            name: DomainName::default(),
             r#type: QType::OPT,
-            udp_payload_size: 4096,
-            ttl: OptTTL::default(),
-            rd_length: 0,
-            rd_data: None,
-        }
-    }
-}
-
-//             +0 (MSB)                            +1 (LSB)
-//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
-// 0: |         EXTENDED-RCODE        |            VERSION            |
-//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
-// 2: | DO|                           Z                               |
-//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
-#[derive(Debug, Default, DnsToNetwork)]
-pub struct OptTTL {
-    extented_rcode: u8, // Forms the upper 8 bits of extended 12-bit RCODE (together with the
-    // 4 bits defined in [RFC1035].  Note that EXTENDED-RCODE value 0
-    // indicates that an unextended RCODE is in use (values 0 through
-    // 15).
-    version: u8, // Indicates the implementation level of the setter.  Full
-    // conformance with this specification is indicated by version '0'.
-    // Requestors are encouraged to set this to the lowest implemented
-    // level capable of expressing a transaction, to minimise the
-    // responder and network load of discovering the greatest common
-    // implementation level between requestor and responder.  A
-    // requestor's version numbering strategy MAY ideally be a run-time
-    // configuration option.
-    // If a responder does not implement the VERSION level of the
-    // request, then it MUST respond with RCODE=BADVERS.  All responses
-    // MUST be limited in format to the VERSION level of the request, but
-    // the VERSION of each response SHOULD be the highest implementation
-    // level of the responder.  In this way, a requestor will learn the
-    // implementation level of a responder as a side effect of every
-    // response, including error responses and including RCODE=BADVERS.
-    z: u16, // zi is D0+Z actually
-}
-
-impl OptTTL {
-    pub fn set_d0(&mut self) {
-        self.z = self.z | 0b1000_0000_0000_0000;
-    }
-}
-
-//             +0 (MSB)                            +1 (LSB)
-//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
-// 0: |                          OPTION-CODE                          |
-//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
-// 2: |                         OPTION-LENGTH                         |
-//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
-// 4: |                                                               |
-//    /                          OPTION-DATA                          /
-//    /                                                               /
-//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
-
-#[derive(Debug, Default, DnsToNetwork)]
-pub struct OptData<'a, T: Debug + ToNetworkOrder> {
-    option_code: u16, // Assigned by the Expert Review process as defined by the DNSEXT
-    // working group and the IESG.
-    option_length: u16,                       // Size (in octets) of OPTION-DATA.
-    option_data: T, // Varies per OPTION-CODE.  MUST be treated as a bit field
-    phantom: std::marker::PhantomData<&'a T>, // the trick for Rust
+            rd_data: Some(RdData::OPT(opt)),
+            ..Default::default()
+        }
+    }
 }
 
 #[cfg(test)]
@@ -850,7 +1302,7 @@ mod tests {
 
     #[test]
     fn dns_packet_header() {
-        const PACKET: &'static str = r#"
+        const PACKET: &str = r#"
 0000   76 86 81 a0 00 01 00 08 00 00 00 01 
         "#;
 
@@ -880,7 +1332,7 @@ mod tests {
 
     #[test]
     fn domain_name_from_position() {
-        const PACKET: &'static str = r#"
+        const PACKET: &str = r#"
     0000   76 86 81 a0 00 01 00 08 00 00 00 01 02 68 6b 00
     0010   00 02 00 01 c0 0c 00 02 00 01 00 00 54 60 00 0e
     0020   01 7a 05 68 6b 69 72 63 03 6e 65 74 c0 0c c0 0c
@@ -899,28 +1351,444 @@ mod tests {
         let cursor = std::io::Cursor::new(&s);
 
         let mut dn = DomainName::default();
-        let i = dn.from_position(12, &cursor.get_ref()).unwrap();
+        let i = dn.from_position(12, cursor.get_ref()).unwrap();
         assert_eq!(i, 16);
         assert_eq!(&dn.to_string(), "hk.");
 
         let mut dn = DomainName::default();
-        let i = dn.from_position(20, &cursor.get_ref()).unwrap();
+        let i = dn.from_position(20, cursor.get_ref()).unwrap();
         assert_eq!(i, 22);
         assert_eq!(&dn.to_string(), "hk.");
 
         let mut dn = DomainName::default();
-        let i = dn.from_position(32, &cursor.get_ref()).unwrap();
+        let i = dn.from_position(32, cursor.get_ref()).unwrap();
         assert_eq!(i, 46);
         assert_eq!(&dn.to_string(), "z.hkirc.net.hk.");
 
         let mut dn = DomainName::default();
-        let i = dn.from_position(58, &cursor.get_ref()).unwrap();
+        let i = dn.from_position(58, cursor.get_ref()).unwrap();
         assert_eq!(i, 62);
         assert_eq!(&dn.to_string(), "d.hkirc.net.hk.");
 
         let mut dn = DomainName::default();
-        let i = dn.from_position(58 + 16, &cursor.get_ref()).unwrap();
+        let i = dn.from_position(58 + 16, cursor.get_ref()).unwrap();
         assert_eq!(i, 62 + 16);
         assert_eq!(&dn.to_string(), "x.hkirc.net.hk.");
     }
+
+    #[test]
+    fn resource_record_rdata() {
+        const PACKET: &str = r#"
+0000   12 34 81 80 00 01 00 04 00 00 00 00 07 65 78 61
+0010   6d 70 6c 65 03 63 6f 6d 00 00 0f 00 01 c0 0c 00
+0020   0f 00 01 00 00 01 2c 00 09 00 0a 04 6d 61 69 6c
+0030   c0 0c c0 0c 00 0f 00 01 00 00 01 2c 00 08 00 14
+0040   03 6d 78 32 c0 2b c0 0c 00 10 00 01 00 00 01 2c
+0050   00 0c 05 68 65 6c 6c 6f 05 77 6f 72 6c 64 c0 0c
+0060   00 06 00 01 00 00 01 2c 00 26 02 6e 73 c0 0c 0a
+0070   68 6f 73 74 6d 61 73 74 65 72 c0 0c 78 86 30 45
+0080   00 00 1c 20 00 00 0e 10 00 12 75 00 00 00 01 2c
+        "#;
+
+        let response = test_from_network!(PACKET, DNSResponse);
+        assert_eq!(response.answer.len(), 4);
+
+        // domain names are compressed the same way
+        let values = test_to_network!(response);
+        assert_eq!(values.0, get_sample_slice(PACKET));
+        assert_eq!(values.1, 144);
+
+        // MX with a pointer to the question name
+        match &response.answer[0].rd_data {
+            Some(RdData::MX(mx)) => {
+                assert_eq!(mx.preference, 10);
+                assert_eq!(&mx.exchange.to_string(), "mail.example.com.");
+            }
+            _ => panic!("MX RR expected"),
+        }
+
+        // MX with a pointer to a pointer
+        match &response.answer[1].rd_data {
+            Some(RdData::MX(mx)) => {
+                assert_eq!(mx.preference, 20);
+                assert_eq!(&mx.exchange.to_string(), "mx2.mail.example.com.");
+            }
+            _ => panic!("MX RR expected"),
+        }
+
+        // TXT with 2 character strings
+        match &response.answer[2].rd_data {
+            Some(RdData::TXT(txt)) => {
+                assert_eq!(txt.len(), 2);
+                assert_eq!(txt[0].data, "hello");
+                assert_eq!(txt[1].data, "world");
+            }
+            _ => panic!("TXT RR expected"),
+        }
+
+        // SOA
+        match &response.answer[3].rd_data {
+            Some(RdData::SOA(soa)) => {
+                assert_eq!(&soa.mname.to_string(), "ns.example.com.");
+                assert_eq!(&soa.rname.to_string(), "hostmaster.example.com.");
+                assert_eq!(soa.serial, 2022060101);
+                assert_eq!(soa.refresh, 7200);
+                assert_eq!(soa.retry, 3600);
+                assert_eq!(soa.expire, 1209600);
+                assert_eq!(soa.minimum, 300);
+            }
+            _ => panic!("SOA RR expected"),
+        }
+    }
+
+    #[test]
+    fn response_authority_additional() {
+        const PACKET: &str = r#"
+0000   ab cd 81 00 00 01 00 00 00 02 00 02 03 77 77 77
+0010   07 65 78 61 6d 70 6c 65 03 63 6f 6d 00 00 01 00
+0020   01 c0 10 00 02 00 01 00 02 a3 00 00 14 01 61 0c
+0030   69 61 6e 61 2d 73 65 72 76 65 72 73 03 6e 65 74
+0040   00 c0 10 00 02 00 01 00 02 a3 00 00 04 01 62 c0
+0050   2f c0 2d 00 01 00 01 00 02 a3 00 00 04 c7 2b 87
+0060   35 00 00 29 04 d0 00 00 80 00 00 00
+        "#;
+
+        let response = test_from_network!(PACKET, DNSResponse);
+        assert!(response.answer.is_empty());
+
+        // compressed names and OPT are written back identically
+        let values = test_to_network!(response);
+        assert_eq!(values.0, get_sample_slice(PACKET));
+
+        // referral
+        let authority = &response.authority;
+        assert_eq!(authority.len(), 2);
+        assert_eq!(&authority[0].name.to_string(), "example.com.");
+        match &authority[1].rd_data {
+            Some(RdData::NS(ns)) => assert_eq!(&ns.to_string(), "b.iana-servers.net."),
+            _ => panic!("NS RR expected"),
+        }
+
+        // glue and OPT
+        let additional = &response.additional;
+        assert_eq!(additional.len(), 2);
+        assert_eq!(&additional[0].name.to_string(), "a.iana-servers.net.");
+        match &additional[0].rd_data {
+            Some(RdData::A(a)) => assert_eq!(*a, 0xC72B8735),
+            _ => panic!("A RR expected"),
+        }
+        assert_eq!(additional[1].r#type, QType::OPT);
+        match &additional[1].rd_data {
+            Some(RdData::OPT(opt)) => assert_eq!(opt.udp_payload_size, 1232),
+            _ => panic!("OPT RR expected"),
+        }
+    }
+
+    #[test]
+    fn unknown_resource_record() {
+        const PACKET: &str = r#"
+0000   01 02 81 80 00 01 00 03 00 00 00 00 07 65 78 61
+0010   6d 70 6c 65 03 63 6f 6d 00 ff 00 00 01 c0 0c ff
+0020   00 00 01 00 00 00 3c 00 04 de ad be ef c0 0c 00
+0030   21 00 01 00 00 00 3c 00 08 00 00 00 05 13 c4 c0
+0040   0c c0 0c 00 01 00 01 00 00 00 3c 00 04 c0 00 02
+0050   01
+        "#;
+
+        let response = test_from_network!(PACKET, DNSResponse);
+        assert_eq!(response.question[0].r#type, QType::Unknown(0xFF00));
+        assert_eq!(response.answer.len(), 3);
+
+        // type code not in the QType enum
+        assert_eq!(response.answer[0].r#type, QType::Unknown(0xFF00));
+        match &response.answer[0].rd_data {
+            Some(RdData::Unknown { type_code, bytes }) => {
+                assert_eq!(*type_code, 0xFF00);
+                assert_eq!(bytes, &[0xDE, 0xAD, 0xBE, 0xEF]);
+            }
+            _ => panic!("unknown RR expected"),
+        }
+
+        // known type but not decoded
+        match &response.answer[1].rd_data {
+            Some(RdData::Unknown { type_code, bytes }) => {
+                assert_eq!(*type_code, 33);
+                assert_eq!(bytes.len(), 8);
+            }
+            _ => panic!("unknown RR expected"),
+        }
+
+        // following RR is still correctly read
+        match &response.answer[2].rd_data {
+            Some(RdData::A(a)) => assert_eq!(*a, 0xC0000201),
+            _ => panic!("A RR expected"),
+        }
+    }
+
+    #[test]
+    fn counts_larger_than_message() {
+        // a header alone, claiming the largest sections possible
+        let bytes = [
+            0x12, 0x34, 0x81, 0x80, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+        let mut message = DNSMessage::default();
+        assert!(message
+            .from_network_bytes(&mut Cursor::new(bytes.as_slice()))
+            .is_err());
+
+        // nothing left for the records
+        let mut cursor = Cursor::new(&bytes[12..]);
+        assert!(DNSMessage::resource_records(&mut cursor, 0xFFFF).is_err());
+    }
+
+    #[test]
+    fn character_string_malformed() {
+        // root name, type, class IN, TTL 60, then RDLENGTH and RDATA
+        fn decode(r#type: u8, rdata: &[u8]) -> DNSResult<DNSResourceRecord> {
+            let mut bytes = vec![0x00, 0x00, r#type, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c];
+            bytes.extend_from_slice(rdata);
+            let mut rr = DNSResourceRecord::default();
+            rr.from_network_bytes(&mut Cursor::new(bytes.as_slice()))?;
+            Ok(rr)
+        }
+
+        fn out_of_range(result: DNSResult<DNSResourceRecord>) -> bool {
+            matches!(
+                result,
+                Err(DNSError::DNSInternalError(
+                    InternalError::DnsOffsetOutOfRange
+                ))
+            )
+        }
+
+        let rr = decode(16, &[0x00, 0x06, 0x05, b'h', b'e', b'l', b'l', b'o']).unwrap();
+        assert!(matches!(rr.rd_data, Some(RdData::TXT(txt)) if txt[0].data == "hello"));
+
+        // the character string goes past RDLENGTH, into what follows
+        assert!(out_of_range(decode(
+            16,
+            &[0x00, 0x04, 0x05, b'h', b'e', b'l', b'l', b'o']
+        )));
+
+        // RDATA cut short
+        assert!(out_of_range(decode(16, &[0x00, 0x06, 0x05, b'h', b'e'])));
+        assert!(out_of_range(decode(13, &[0x00, 0x06, 0x05, b'h', b'e'])));
+        assert!(out_of_range(decode(13, &[0x00, 0x03, 0x01, b'x', 0x01])));
+
+        // a CNAME running past RDLENGTH, or stopping short of it
+        let rr = decode(5, &[0x00, 0x03, 0x01, b'x', 0x00]).unwrap();
+        assert!(matches!(rr.rd_data, Some(RdData::CNAME(cname)) if cname.labels == ["x"]));
+        assert!(matches!(
+            decode(5, &[0x00, 0x02, 0x01, b'x', 0x00]),
+            Err(DNSError::DNS(_))
+        ));
+        assert!(matches!(
+            decode(5, &[0x00, 0x04, 0x01, b'x', 0x00, 0x00]),
+            Err(DNSError::DNS(_))
+        ));
+    }
+
+    #[test]
+    fn domain_name_malformed() {
+        fn decode(bytes: &[u8], pos: usize) -> DNSResult<usize> {
+            let mut dn = DomainName::default();
+            dn.from_position(pos, &bytes)
+        }
+
+        fn internal_error(result: DNSResult<usize>) -> InternalError {
+            match result {
+                Err(DNSError::DNSInternalError(e)) => e,
+                _ => panic!("internal error expected"),
+            }
+        }
+
+        // truncated label
+        let bytes = [0x03_u8, 0x77, 0x77];
+        assert_eq!(
+            internal_error(decode(&bytes, 0)),
+            InternalError::DnsOffsetOutOfRange
+        );
+
+        // missing sentinel
+        let bytes = [0x01_u8, 0x77];
+        assert_eq!(
+            internal_error(decode(&bytes, 0)),
+            InternalError::DnsOffsetOutOfRange
+        );
+
+        // starting past the end
+        assert_eq!(
+            internal_error(decode(&bytes, 10)),
+            InternalError::DnsOffsetOutOfRange
+        );
+
+        // truncated pointer
+        let bytes = [0x00_u8, 0xC0];
+        assert_eq!(
+            internal_error(decode(&bytes, 1)),
+            InternalError::DnsOffsetOutOfRange
+        );
+
+        // pointer to itself and forward
+        let bytes = [0xC0_u8, 0x00];
+        assert_eq!(
+            internal_error(decode(&bytes, 0)),
+            InternalError::DnsForwardPointer
+        );
+        let bytes = [0xC0_u8, 0x02, 0x00];
+        assert_eq!(
+            internal_error(decode(&bytes, 0)),
+            InternalError::DnsForwardPointer
+        );
+
+        // a label followed by a pointer to that label
+        let bytes = [0x01_u8, 0x77, 0xC0, 0x00];
+        assert_eq!(
+            internal_error(decode(&bytes, 0)),
+            InternalError::DnsPointerLoop
+        );
+
+        // 2 pointers pointing to each other: the first one we follow is fine, not the next one
+        let bytes = [0x01_u8, 0x61, 0xC0, 0x04, 0x01, 0x62, 0xC0, 0x00];
+        assert_eq!(
+            internal_error(decode(&bytes, 4)),
+            InternalError::DnsForwardPointer
+        );
+
+        // label too long, or reserved label types
+        let mut bytes = vec![64_u8];
+        bytes.extend([0x77; 64]);
+        bytes.push(0);
+        assert_eq!(
+            internal_error(decode(&bytes, 0)),
+            InternalError::DnsLabelTooLong
+        );
+        let bytes = [0x80_u8, 0x00];
+        assert_eq!(
+            internal_error(decode(&bytes, 0)),
+            InternalError::DnsLabelTooLong
+        );
+
+        // domain name too long: 4 labels of 63 chars = 256 octets with the sentinel
+        let mut bytes = Vec::new();
+        for _ in 0..4 {
+            bytes.push(63_u8);
+            bytes.extend([0x77; 63]);
+        }
+        bytes.push(0);
+        assert_eq!(
+            internal_error(decode(&bytes, 0)),
+            InternalError::DnsDomainNameTooLong
+        );
+
+        // but 255 octets is fine
+        let mut bytes = Vec::new();
+        for _ in 0..3 {
+            bytes.push(63_u8);
+            bytes.extend([0x77; 63]);
+        }
+        bytes.push(61_u8);
+        bytes.extend([0x77; 61]);
+        bytes.push(0);
+        assert_eq!(decode(&bytes, 0).unwrap(), 255);
+    }
+
+    #[test]
+    fn message_round_trip() {
+        // a query captured with OPT
+        const PACKET: &str = r#"
+0000   5a 5a 01 20 00 01 00 00 00 00 00 01 03 77 77 77
+0010   07 65 78 61 6d 70 6c 65 03 63 6f 6d 00 00 1c 00
+0020   01 00 00 29 04 d0 00 00 00 00 00 00
+        "#;
+
+        let query = test_from_network!(PACKET, DNSMessage);
+        assert_eq!(query.header.flags.packet_type, PacketType::Query);
+        assert_eq!(query.question.len(), 1);
+        assert_eq!(&query.question[0].name.to_string(), "www.example.com.");
+        assert_eq!(query.question[0].r#type, QType::AAAA);
+        assert_eq!(query.additional.len(), 1);
+        assert_eq!(query.additional[0].r#type, QType::OPT);
+
+        let values = test_to_network!(query);
+        assert_eq!(values.0, get_sample_slice(PACKET));
+
+        // build a response to this query
+        let mut response = DNSMessage::default();
+        response.header.id = query.header.id;
+        response.header.flags.packet_type = PacketType::Response;
+        response.push_question(DNSQuestion::new("www.example.com", QType::AAAA, None).unwrap());
+        response.push_answer(DNSResourceRecord {
+            name: DomainName::try_from("www.example.com").unwrap(),
+            r#type: QType::AAAA,
+            class: QClass::IN,
+            ttl: 3600,
+            rd_length: 16,
+            rd_data: Some(RdData::AAAA([
+                0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+            ])),
+        });
+        response.push_additional(DNSResourceRecord::from(OPT::default()));
+
+        let (buffer, length) = test_to_network!(response);
+        assert_eq!(length, buffer.len());
+
+        // owner name is a pointer to the question
+        assert_eq!(&buffer[33..35], &[0xC0, 0x0C]);
+
+        let mut parsed = DNSMessage::default();
+        parsed
+            .from_network_bytes(&mut std::io::Cursor::new(buffer.as_slice()))
+            .unwrap();
+        assert_eq!(parsed.header.id, 0x5A5A);
+        assert_eq!(parsed.header.an_count, 1);
+        assert_eq!(parsed.header.ar_count, 1);
+        assert_eq!(parsed.answer[0].ttl, 3600);
+        match &parsed.answer[0].rd_data {
+            Some(RdData::AAAA(aaaa)) => assert_eq!(aaaa[15], 1),
+            _ => panic!("AAAA RR expected"),
+        }
+        match &parsed.additional[0].rd_data {
+            Some(RdData::OPT(opt)) => assert_eq!(opt.udp_payload_size, 4096),
+            _ => panic!("OPT RR expected"),
+        }
+    }
+
+    #[test]
+    fn domain_name_compression_dotted_label() {
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut table = CompressionTable::new(0);
+
+        // a wire label containing a dot
+        let dotted = DomainName {
+            labels: vec![String::from("a.b"), String::from("c")],
+        };
+        dotted
+            .to_network_bytes_compressed(&mut buffer, &mut table)
+            .unwrap();
+
+        // a.b.c as 3 labels is a different name: only the common suffix c is pointed to
+        let dn = DomainName::try_from("a.b.c").unwrap();
+        let length = dn
+            .to_network_bytes_compressed(&mut buffer, &mut table)
+            .unwrap();
+        assert_eq!(length, 6);
+        assert_eq!(&buffer[7..], &[0x01, b'a', 0x01, b'b', 0xC0, 0x04]);
+    }
+
+    #[test]
+    fn resource_record_rdata_too_long() {
+        let rr = DNSResourceRecord {
+            r#type: QType::Unknown(65280),
+            rd_data: Some(RdData::Unknown {
+                type_code: 65280,
+                bytes: vec![0; 70000],
+            }),
+            ..Default::default()
+        };
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let err = rr.to_network_bytes(&mut buffer).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
 }
@@ -0,0 +1,832 @@
+//! EDNS(0) structures. Taken from https://datatracker.ietf.org/doc/html/rfc6891
+//!
+//! The OPT pseudo-RR is carried in the additional section. Its RDATA is a list of options,
+//! each one being decoded into its own type when known.
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use dns_derive::{DnsEnum, DnsFromNetwork, DnsToNetwork};
+
+use crate::error::{DNSError, DNSResult};
+use crate::network_order::{FromNetworkOrder, ToNetworkOrder};
+
+// OPT RR: https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.2
+// RR format
+// +------------+--------------+------------------------------+
+// | Field Name | Field Type   | Description                  |
+// +------------+--------------+------------------------------+
+// | NAME       | domain name  | MUST be 0 (root domain)      |
+// | TYPE       | u_int16_t    | OPT (41)                     |
+// | CLASS      | u_int16_t    | requestor's UDP payload size |
+// | TTL        | u_int32_t    | extended RCODE and flags     |
+// | RDLEN      | u_int16_t    | length of all RDATA          |
+// | RDATA      | octet stream | {attribute,value} pairs      |
+// +------------+--------------+------------------------------+
+//
+// NAME, TYPE and RDLEN are handled by the resource record holding the OPT
+#[derive(Debug, Clone, PartialEq)]
+pub struct OPT {
+    pub udp_payload_size: u16,    // requestor's UDP payload size
+    pub ttl: OptTTL,              // extended RCODE and flags
+    pub options: Vec<EdnsOption>, // {attribute,value} pairs
+}
+
+impl Default for OPT {
+    fn default() -> Self {
+        Self {
+            udp_payload_size: 4096,
+            ttl: OptTTL::default(),
+            options: Vec::new(),
+        }
+    }
+}
+
+impl OPT {
+    // Add an option to the RDATA
+    pub fn push_option(&mut self, option: EdnsOption) {
+        self.options.push(option);
+    }
+
+    // Replace all options having the same code by this one
+    pub fn set_option(&mut self, option: EdnsOption) {
+        let code = option.code();
+        self.options.retain(|o| o.code() != code);
+        self.options.push(option);
+    }
+
+    // The padding option, if any
+    pub fn padding_mut(&mut self) -> Option<&mut Padding> {
+        self.options.iter_mut().find_map(|o| match o {
+            EdnsOption::Padding(padding) => Some(padding),
+            _ => None,
+        })
+    }
+
+    /// Return the first option having the given code, if any
+    ///
+    /// ```
+    /// use dnslib::rfc6891::{EdnsOption, OptionCode, OPT};
+    ///
+    /// let mut opt = OPT::default();
+    /// opt.push_option(EdnsOption::Unknown { option_code: 65001, bytes: vec![1, 2] });
+    /// assert!(opt.option(OptionCode::Unknown(65001)).is_some());
+    /// assert!(opt.option(OptionCode::NSID).is_none());
+    /// ```
+    pub fn option(&self, code: OptionCode) -> Option<&EdnsOption> {
+        self.options.iter().find(|o| o.code() == code)
+    }
+}
+
+//             +0 (MSB)                            +1 (LSB)
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 0: |         EXTENDED-RCODE        |            VERSION            |
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 2: | DO|                           Z                               |
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+#[derive(Debug, Default, Clone, Copy, PartialEq, DnsToNetwork, DnsFromNetwork)]
+pub struct OptTTL {
+    // Forms the upper 8 bits of extended 12-bit RCODE (together with the
+    // 4 bits defined in [RFC1035]. Note that EXTENDED-RCODE value 0
+    // indicates that an unextended RCODE is in use (values 0 through 15).
+    pub extended_rcode: u8,
+
+    // Indicates the implementation level of the setter. Full conformance with
+    // this specification is indicated by version '0'.
+    pub version: u8,
+
+    // DO bit (RFC3225) and Z bits, which must be set to zero
+    pub flags: u16,
+}
+
+// DNSSEC OK bit: https://datatracker.ietf.org/doc/html/rfc3225#section-3
+const DO_BIT: u16 = 0b1000_0000_0000_0000;
+
+impl OptTTL {
+    /// ```
+    /// use dnslib::rfc6891::OptTTL;
+    ///
+    /// let mut ttl = OptTTL::default();
+    /// assert!(!ttl.dnssec_ok());
+    ///
+    /// ttl.set_dnssec_ok(true);
+    /// assert!(ttl.dnssec_ok());
+    /// assert_eq!(ttl.flags, 0x8000);
+    ///
+    /// ttl.set_dnssec_ok(false);
+    /// assert_eq!(ttl.flags, 0);
+    /// ```
+    pub fn dnssec_ok(&self) -> bool {
+        self.flags & DO_BIT != 0
+    }
+
+    pub fn set_dnssec_ok(&mut self, dnssec_ok: bool) {
+        if dnssec_ok {
+            self.flags |= DO_BIT;
+        } else {
+            self.flags &= !DO_BIT;
+        }
+    }
+}
+
+// EDNS option codes: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-11
+/// ```
+/// use std::str::FromStr;
+/// use dnslib::rfc6891::OptionCode;
+///
+/// assert_eq!(OptionCode::try_from(10u16).unwrap(), OptionCode::COOKIE);
+/// assert_eq!(OptionCode::try_from(65001u16).unwrap(), OptionCode::Unknown(65001));
+/// assert_eq!(OptionCode::from_str("NSID").unwrap(), OptionCode::NSID);
+/// assert!(OptionCode::from_str("NSID65001").is_err());
+/// assert_eq!(u16::from(OptionCode::EDE), 15);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, DnsEnum)]
+#[repr(u16)]
+pub enum OptionCode {
+    LLQ = 1,            // LLQ	[RFC8764]
+    UL = 2,             // Update Lease	[draft-ietf-dnssd-update-lease]
+    NSID = 3,           // NSID	[RFC5001]
+    DAU = 5,            // DNSSEC Algorithm Understood	[RFC6975]
+    DHU = 6,            // DS Hash Understood	[RFC6975]
+    N3U = 7,            // NSEC3 Hash Understood	[RFC6975]
+    ECS = 8,            // edns-client-subnet	[RFC7871]
+    EXPIRE = 9,         // EDNS EXPIRE	[RFC7314]
+    COOKIE = 10,        // COOKIE	[RFC7873]
+    KEEPALIVE = 11,     // edns-tcp-keepalive	[RFC7828]
+    PADDING = 12,       // Padding	[RFC7830]
+    CHAIN = 13,         // CHAIN	[RFC7901]
+    KEYTAG = 14,        // edns-key-tag	[RFC8145]
+    EDE = 15,           // Extended DNS Error	[RFC8914]
+    CLIENTTAG = 16,     // EDNS-Client-Tag	[draft-bellis-dnsop-edns-tags]
+    SERVERTAG = 17,     // EDNS-Server-Tag	[draft-bellis-dnsop-edns-tags]
+    REPORTCHANNEL = 18, // Report-Channel	[RFC9567]
+    ZONEVERSION = 19,   // ZONEVERSION	[RFC9660]
+    Unknown(u16),       // any option code not listed above
+}
+
+//             +0 (MSB)                            +1 (LSB)
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 0: |                          OPTION-CODE                          |
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 2: |                         OPTION-LENGTH                         |
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 4: |                                                               |
+//    /                          OPTION-DATA                          /
+//    /                                                               /
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+//
+// Each variant holds the decoded OPTION-DATA. OPTION-CODE and OPTION-LENGTH are
+// deduced from the variant and its data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdnsOption {
+    ClientSubnet(ClientSubnet),
+    Cookie(Cookie),
+    ExtendedError(ExtendedError),
+    // empty in queries, server identifier in responses (RFC5001)
+    Nsid(Vec<u8>),
+    Padding(Padding),
+    // idle timeout in units of 100 ms, only in responses (RFC7828)
+    TcpKeepalive(Option<u16>),
+    // OPTION-DATA for options we don't decode, kept as is
+    Unknown { option_code: u16, bytes: Vec<u8> },
+}
+
+impl Default for EdnsOption {
+    fn default() -> Self {
+        EdnsOption::Unknown {
+            option_code: 0,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl EdnsOption {
+    // OPTION-CODE of the option
+    pub fn code(&self) -> OptionCode {
+        match self {
+            EdnsOption::ClientSubnet(_) => OptionCode::ECS,
+            EdnsOption::Cookie(_) => OptionCode::COOKIE,
+            EdnsOption::ExtendedError(_) => OptionCode::EDE,
+            EdnsOption::Nsid(_) => OptionCode::NSID,
+            EdnsOption::Padding(_) => OptionCode::PADDING,
+            EdnsOption::TcpKeepalive(_) => OptionCode::KEEPALIVE,
+            EdnsOption::Unknown { option_code, .. } => OptionCode::try_from(*option_code).unwrap(),
+        }
+    }
+}
+
+// Client subnet option: https://datatracker.ietf.org/doc/html/rfc7871#section-6
+//                +0 (MSB)                            +1 (LSB)
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 0: |                            FAMILY                             |
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 2: |     SOURCE PREFIX-LENGTH      |     SCOPE PREFIX-LENGTH       |
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 4: |                           ADDRESS...                          /
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ClientSubnet {
+    pub family: u16,              // 1 for IPv4, 2 for IPv6 (address family numbers)
+    pub source_prefix_length: u8, // leftmost number of significant bits of ADDRESS in the query
+    pub scope_prefix_length: u8,  // leftmost number of significant bits the response covers
+    pub address: Vec<u8>,         // address truncated to SOURCE PREFIX-LENGTH bits
+}
+
+// Address family numbers: https://www.iana.org/assignments/address-family-numbers
+const FAMILY_IPV4: u16 = 1;
+const FAMILY_IPV6: u16 = 2;
+
+impl ClientSubnet {
+    /// Build the option for a query from an address and a source prefix length. Only the
+    /// significant bytes are kept, bits beyond the prefix being cleared.
+    ///
+    /// ```
+    /// use std::net::IpAddr;
+    /// use dnslib::rfc6891::ClientSubnet;
+    ///
+    /// let ecs = ClientSubnet::new("192.0.2.77".parse().unwrap(), 22).unwrap();
+    /// assert_eq!(ecs.family, 1);
+    /// assert_eq!(ecs.address, &[192, 0, 0]);
+    ///
+    /// let ecs = ClientSubnet::new("2001:db8::1".parse().unwrap(), 0).unwrap();
+    /// assert_eq!(ecs.family, 2);
+    /// assert!(ecs.address.is_empty());
+    ///
+    /// assert!(ClientSubnet::new("192.0.2.77".parse().unwrap(), 33).is_err());
+    /// ```
+    pub fn new(address: IpAddr, source_prefix_length: u8) -> DNSResult<Self> {
+        let (family, octets) = match address {
+            IpAddr::V4(ip) => (FAMILY_IPV4, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (FAMILY_IPV6, ip.octets().to_vec()),
+        };
+
+        if source_prefix_length as usize > octets.len() * 8 {
+            return Err(DNSError::new(&format!(
+                "prefix length {} is too long for address {}",
+                source_prefix_length, address
+            )));
+        }
+
+        // keep only the bytes covered by the prefix, and clear bits beyond it
+        let length = (source_prefix_length as usize).div_ceil(8);
+        let mut truncated = octets[..length].to_vec();
+        if !source_prefix_length.is_multiple_of(8) {
+            truncated[length - 1] &= 0xFF << (8 - source_prefix_length % 8);
+        }
+
+        Ok(Self {
+            family,
+            source_prefix_length,
+            scope_prefix_length: 0,
+            address: truncated,
+        })
+    }
+
+    /// The address, padded with zeros to its full length
+    ///
+    /// ```
+    /// use std::net::IpAddr;
+    /// use dnslib::rfc6891::ClientSubnet;
+    ///
+    /// let ecs = ClientSubnet::new("192.0.2.77".parse().unwrap(), 24).unwrap();
+    /// assert_eq!(ecs.ip().unwrap(), "192.0.2.0".parse::<IpAddr>().unwrap());
+    /// ```
+    pub fn ip(&self) -> DNSResult<IpAddr> {
+        match self.family {
+            FAMILY_IPV4 if self.address.len() <= 4 => {
+                let mut octets = [0u8; 4];
+                octets[..self.address.len()].copy_from_slice(&self.address);
+                Ok(IpAddr::from(octets))
+            }
+            FAMILY_IPV6 if self.address.len() <= 16 => {
+                let mut octets = [0u8; 16];
+                octets[..self.address.len()].copy_from_slice(&self.address);
+                Ok(IpAddr::from(octets))
+            }
+            _ => Err(DNSError::new(&format!(
+                "invalid client subnet family {} or address length {}",
+                self.family,
+                self.address.len()
+            ))),
+        }
+    }
+}
+
+/// A subnet as address/prefix length. Without prefix length, the whole address is used.
+///
+/// ```
+/// use std::str::FromStr;
+/// use dnslib::rfc6891::ClientSubnet;
+///
+/// let ecs = ClientSubnet::from_str("192.0.2.0/24").unwrap();
+/// assert_eq!(ecs.source_prefix_length, 24);
+/// assert_eq!(ecs.address, &[192, 0, 2]);
+///
+/// let ecs = ClientSubnet::from_str("2001:db8::/56").unwrap();
+/// assert_eq!(ecs.family, 2);
+/// assert_eq!(ecs.address, &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0]);
+///
+/// let ecs = ClientSubnet::from_str("192.0.2.1").unwrap();
+/// assert_eq!(ecs.source_prefix_length, 32);
+///
+/// assert!(ClientSubnet::from_str("192.0.2.0/").is_err());
+/// assert!(ClientSubnet::from_str("foo/24").is_err());
+/// ```
+impl FromStr for ClientSubnet {
+    type Err = DNSError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+
+        let address = address
+            .parse::<IpAddr>()
+            .map_err(|e| DNSError::new(&format!("invalid subnet '{}': {}", s, e)))?;
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .map_err(|e| DNSError::new(&format!("invalid subnet '{}': {}", s, e)))?,
+            None if address.is_ipv4() => 32,
+            None => 128,
+        };
+
+        ClientSubnet::new(address, prefix)
+    }
+}
+
+// Cookie option: https://datatracker.ietf.org/doc/html/rfc7873#section-4
+//                +0 (MSB)                            +1 (LSB)
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 0: |                                                               |
+//    +                                                               +
+//    /                         Client Cookie                         /
+//    +                                                               +
+//    |                                                               |
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 8: |                                                               |
+//    /            Server Cookie (8 to 32 bytes, optional)            /
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Cookie {
+    pub client: [u8; CLIENT_COOKIE_LENGTH],
+    pub server: Vec<u8>,
+}
+
+pub const CLIENT_COOKIE_LENGTH: usize = 8;
+pub const MIN_SERVER_COOKIE_LENGTH: usize = 8;
+pub const MAX_SERVER_COOKIE_LENGTH: usize = 32;
+
+impl Cookie {
+    /// A cookie with a random client cookie and no server cookie yet
+    ///
+    /// ```
+    /// use dnslib::rfc6891::Cookie;
+    ///
+    /// let cookie = Cookie::new();
+    /// assert!(cookie.server.is_empty());
+    /// assert_ne!(cookie.client, Cookie::new().client);
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            client: rand::random(),
+            server: Vec::new(),
+        }
+    }
+}
+
+// Padding option: https://datatracker.ietf.org/doc/html/rfc7830#section-3
+//
+// The padding octets are all zeros. With a block length, the padding length is computed
+// when the message is written, so that the message length is a multiple of the block
+// length (RFC8467).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Padding {
+    pub length: u16,
+    pub block_length: Option<u16>,
+}
+
+// Block lengths recommended by RFC8467
+pub const QUERY_PADDING_BLOCK_LENGTH: u16 = 128;
+pub const RESPONSE_PADDING_BLOCK_LENGTH: u16 = 468;
+
+impl Padding {
+    // Padding computed when the message is written
+    pub fn block(block_length: u16) -> Self {
+        Self {
+            length: 0,
+            block_length: Some(block_length),
+        }
+    }
+
+    /// Number of padding octets needed to get a multiple of the block length, when the
+    /// message length includes the padding option with no octets.
+    ///
+    /// ```
+    /// use dnslib::rfc6891::Padding;
+    ///
+    /// assert_eq!(Padding::block(128).padding_length(100), 28);
+    /// assert_eq!(Padding::block(128).padding_length(128), 0);
+    /// assert_eq!(Padding::block(468).padding_length(470), 466);
+    /// assert_eq!(Padding { length: 10, block_length: None }.padding_length(100), 10);
+    /// ```
+    pub fn padding_length(&self, message_length: usize) -> u16 {
+        match self.block_length {
+            Some(block) if block > 0 => {
+                let block = block as usize;
+                ((block - message_length % block) % block) as u16
+            }
+            _ => self.length,
+        }
+    }
+}
+
+// Extended DNS Error option: https://datatracker.ietf.org/doc/html/rfc8914#section-2
+//                                              1   1   1   1   1   1
+//      0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 0: |                           INFO-CODE                           |
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+// 2: / EXTRA-TEXT ...                                                /
+//    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExtendedError {
+    pub info_code: InfoCode,
+    pub extra_text: String, // UTF-8 text for humans, possibly empty
+}
+
+// Extended DNS Error codes: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#extended-dns-error-codes
+/// ```
+/// use dnslib::rfc6891::InfoCode;
+///
+/// assert_eq!(InfoCode::try_from(6u16).unwrap(), InfoCode::DnssecBogus);
+/// assert_eq!(InfoCode::DnssecBogus.to_string(), "DNSSEC Bogus");
+/// assert_eq!(InfoCode::try_from(49152u16).unwrap(), InfoCode::Unknown(49152));
+/// assert_eq!(InfoCode::Unknown(49152).to_string(), "Unknown Error");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, DnsEnum)]
+#[repr(u16)]
+pub enum InfoCode {
+    OtherError = 0,                   // Other Error	[RFC8914]
+    UnsupportedDnskeyAlgorithm = 1,   // Unsupported DNSKEY Algorithm	[RFC8914]
+    UnsupportedDsDigestType = 2,      // Unsupported DS Digest Type	[RFC8914]
+    StaleAnswer = 3,                  // Stale Answer	[RFC8914][RFC8767]
+    ForgedAnswer = 4,                 // Forged Answer	[RFC8914]
+    DnssecIndeterminate = 5,          // DNSSEC Indeterminate	[RFC8914]
+    DnssecBogus = 6,                  // DNSSEC Bogus	[RFC8914]
+    SignatureExpired = 7,             // Signature Expired	[RFC8914]
+    SignatureNotYetValid = 8,         // Signature Not Yet Valid	[RFC8914]
+    DnskeyMissing = 9,                // DNSKEY Missing	[RFC8914]
+    RrsigsMissing = 10,               // RRSIGs Missing	[RFC8914]
+    NoZoneKeyBitSet = 11,             // No Zone Key Bit Set	[RFC8914]
+    NsecMissing = 12,                 // NSEC Missing	[RFC8914]
+    CachedError = 13,                 // Cached Error	[RFC8914]
+    NotReady = 14,                    // Not Ready.	[RFC8914]
+    Blocked = 15,                     // Blocked	[RFC8914]
+    Censored = 16,                    // Censored	[RFC8914]
+    Filtered = 17,                    // Filtered	[RFC8914]
+    Prohibited = 18,                  // Prohibited	[RFC8914]
+    StaleNxdomainAnswer = 19,         // Stale NXDomain Answer	[RFC8914]
+    NotAuthoritative = 20,            // Not Authoritative	[RFC8914]
+    NotSupported = 21,                // Not Supported	[RFC8914]
+    NoReachableAuthority = 22,        // No Reachable Authority	[RFC8914]
+    NetworkError = 23,                // Network Error	[RFC8914]
+    InvalidData = 24,                 // Invalid Data	[RFC8914]
+    SignatureExpiredBeforeValid = 25, // Signature Expired before Valid	[RFC9077]
+    TooEarly = 26,                    // Too Early	[RFC9250]
+    UnsupportedNsec3Iterations = 27,  // Unsupported NSEC3 Iterations Value	[RFC9276]
+    UnableToConformToPolicy = 28,     // Unable to conform to policy	[draft-homburg-dnsop-codcp]
+    Synthesized = 29,                 // Synthesized	[https://github.com/PowerDNS/pdns/pull/12334]
+    InvalidQueryType = 30, // Invalid Query Type	[RFC-ietf-dnsop-compact-denial-of-existence-07]
+    Unknown(u16),          // unassigned or private use
+}
+
+// Purpose as given in the registry
+impl fmt::Display for InfoCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let purpose = match self {
+            InfoCode::OtherError => "Other Error",
+            InfoCode::UnsupportedDnskeyAlgorithm => "Unsupported DNSKEY Algorithm",
+            InfoCode::UnsupportedDsDigestType => "Unsupported DS Digest Type",
+            InfoCode::StaleAnswer => "Stale Answer",
+            InfoCode::ForgedAnswer => "Forged Answer",
+            InfoCode::DnssecIndeterminate => "DNSSEC Indeterminate",
+            InfoCode::DnssecBogus => "DNSSEC Bogus",
+            InfoCode::SignatureExpired => "Signature Expired",
+            InfoCode::SignatureNotYetValid => "Signature Not Yet Valid",
+            InfoCode::DnskeyMissing => "DNSKEY Missing",
+            InfoCode::RrsigsMissing => "RRSIGs Missing",
+            InfoCode::NoZoneKeyBitSet => "No Zone Key Bit Set",
+            InfoCode::NsecMissing => "NSEC Missing",
+            InfoCode::CachedError => "Cached Error",
+            InfoCode::NotReady => "Not Ready",
+            InfoCode::Blocked => "Blocked",
+            InfoCode::Censored => "Censored",
+            InfoCode::Filtered => "Filtered",
+            InfoCode::Prohibited => "Prohibited",
+            InfoCode::StaleNxdomainAnswer => "Stale NXDomain Answer",
+            InfoCode::NotAuthoritative => "Not Authoritative",
+            InfoCode::NotSupported => "Not Supported",
+            InfoCode::NoReachableAuthority => "No Reachable Authority",
+            InfoCode::NetworkError => "Network Error",
+            InfoCode::InvalidData => "Invalid Data",
+            InfoCode::SignatureExpiredBeforeValid => "Signature Expired before Valid",
+            InfoCode::TooEarly => "Too Early",
+            InfoCode::UnsupportedNsec3Iterations => "Unsupported NSEC3 Iterations Value",
+            InfoCode::UnableToConformToPolicy => "Unable to conform to policy",
+            InfoCode::Synthesized => "Synthesized",
+            InfoCode::InvalidQueryType => "Invalid Query Type",
+            InfoCode::Unknown(_) => "Unknown Error",
+        };
+        write!(f, "{}", purpose)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rfc1035::{DNSResourceRecord, QType, RdData};
+    use crate::util::get_sample_slice;
+    use crate::{test_from_network, test_to_network};
+
+    #[test]
+    fn opt_resource_record() {
+        // OPT with DO bit, a cookie and a private option
+        const PACKET: &str = r#"
+0000   00 00 29 04 d0 01 00 80 00 00 14 00 0a 00 08 24
+0010   a1 64 c4 5e 69 62 d1 fd e9 00 04 de ad be ef
+        "#;
+
+        let rr = test_from_network!(PACKET, DNSResourceRecord);
+        assert_eq!(rr.r#type, QType::OPT);
+        assert_eq!(rr.rd_length, 20);
+
+        let opt = match &rr.rd_data {
+            Some(RdData::OPT(opt)) => opt,
+            _ => panic!("OPT RR expected"),
+        };
+        assert_eq!(opt.udp_payload_size, 1232);
+        assert_eq!(opt.ttl.extended_rcode, 1);
+        assert_eq!(opt.ttl.version, 0);
+        assert!(opt.ttl.dnssec_ok());
+        assert_eq!(opt.options.len(), 2);
+        assert_eq!(opt.options[0].code(), OptionCode::COOKIE);
+        assert_eq!(
+            opt.options[1],
+            EdnsOption::Unknown {
+                option_code: 65001,
+                bytes: vec![0xde, 0xad, 0xbe, 0xef]
+            }
+        );
+        assert_eq!(
+            opt.option(OptionCode::COOKIE),
+            Some(&EdnsOption::Cookie(Cookie {
+                client: [0x24, 0xa1, 0x64, 0xc4, 0x5e, 0x69, 0x62, 0xd1],
+                server: Vec::new()
+            }))
+        );
+
+        // to
+        let values = test_to_network!(rr);
+        assert_eq!(values.0, get_sample_slice(PACKET));
+        assert_eq!(values.1, 31);
+
+        // built from scratch
+        let mut opt = OPT {
+            udp_payload_size: 1232,
+            ..OPT::default()
+        };
+        opt.ttl.extended_rcode = 1;
+        opt.ttl.set_dnssec_ok(true);
+        opt.push_option(EdnsOption::Cookie(Cookie {
+            client: [0x24, 0xa1, 0x64, 0xc4, 0x5e, 0x69, 0x62, 0xd1],
+            server: Vec::new(),
+        }));
+        opt.push_option(EdnsOption::Unknown {
+            option_code: 65001,
+            bytes: vec![0xde, 0xad, 0xbe, 0xef],
+        });
+        let rr = DNSResourceRecord::from(opt);
+        let values = test_to_network!(rr);
+        assert_eq!(values.0, get_sample_slice(PACKET));
+    }
+
+    #[test]
+    fn client_subnet() {
+        // ECS option in a response: 192.0.2.0/24 with scope /16
+        const PACKET: &str = r#"
+0000   00 08 00 07 00 01 18 10 c0 00 02
+        "#;
+
+        let option = test_from_network!(PACKET, EdnsOption);
+        let ecs = match &option {
+            EdnsOption::ClientSubnet(ecs) => ecs,
+            _ => panic!("ECS option expected"),
+        };
+        assert_eq!(ecs.family, 1);
+        assert_eq!(ecs.source_prefix_length, 24);
+        assert_eq!(ecs.scope_prefix_length, 16);
+        assert_eq!(ecs.ip().unwrap().to_string(), "192.0.2.0");
+
+        // to
+        let values = test_to_network!(option);
+        assert_eq!(values.0, get_sample_slice(PACKET));
+        assert_eq!(values.1, 11);
+
+        // the query sends the same option with a zero scope
+        let mut query = EdnsOption::ClientSubnet(ClientSubnet::from_str("192.0.2.1/24").unwrap());
+        let values = test_to_network!(query);
+        assert_eq!(values.0[6], 24);
+        assert_eq!(values.0[7], 0);
+        assert_eq!(&values.0[8..], &[192, 0, 2]);
+
+        // IPv6 /0 has no address at all
+        query = EdnsOption::ClientSubnet(ClientSubnet::from_str("::/0").unwrap());
+        let values = test_to_network!(query);
+        assert_eq!(values.0, &[0x00, 0x08, 0x00, 0x04, 0x00, 0x02, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn cookie() {
+        // client and server cookies
+        const PACKET: &str = r#"
+0000   00 0a 00 18 24 a1 64 c4 5e 69 62 d1 01 00 00 00
+0010   63 2c 5e 1f 3e 9a 8c 43 1e 35 6f 0a
+        "#;
+
+        let option = test_from_network!(PACKET, EdnsOption);
+        let cookie = match &option {
+            EdnsOption::Cookie(cookie) => cookie,
+            _ => panic!("COOKIE option expected"),
+        };
+        assert_eq!(
+            cookie.client,
+            [0x24, 0xa1, 0x64, 0xc4, 0x5e, 0x69, 0x62, 0xd1]
+        );
+        assert_eq!(cookie.server.len(), 16);
+        assert_eq!(cookie.server[0], 1);
+
+        // to
+        let values = test_to_network!(option);
+        assert_eq!(values.0, get_sample_slice(PACKET));
+        assert_eq!(values.1, 28);
+
+        // client cookie only
+        let b = get_sample_slice(PACKET);
+        let mut bytes = b[..12].to_vec();
+        bytes[3] = 8;
+        let mut option = EdnsOption::default();
+        option
+            .from_network_bytes(&mut std::io::Cursor::new(bytes.as_slice()))
+            .unwrap();
+        assert_eq!(option.code(), OptionCode::COOKIE);
+
+        // server cookie too short
+        let mut bytes = b[..16].to_vec();
+        bytes[3] = 12;
+        let mut option = EdnsOption::default();
+        assert!(option
+            .from_network_bytes(&mut std::io::Cursor::new(bytes.as_slice()))
+            .is_err());
+    }
+
+    #[test]
+    fn extended_error() {
+        // DNSSEC Bogus with extra text
+        const PACKET: &str = r#"
+0000   00 0f 00 14 00 06 73 69 67 6e 61 74 75 72 65 20
+0010   65 78 70 69 72 65 64 00
+        "#;
+
+        let option = test_from_network!(PACKET, EdnsOption);
+        let ede = match &option {
+            EdnsOption::ExtendedError(ede) => ede,
+            _ => panic!("EDE option expected"),
+        };
+        assert_eq!(ede.info_code, InfoCode::DnssecBogus);
+        assert_eq!(ede.extra_text, "signature expired");
+
+        // to: the trailing NUL is not written back
+        let values = test_to_network!(option);
+        assert_eq!(&values.0[4..], &get_sample_slice(PACKET)[4..23]);
+        assert_eq!(values.0[3], 19);
+
+        // unassigned code without text
+        let option = EdnsOption::ExtendedError(ExtendedError {
+            info_code: InfoCode::Unknown(500),
+            extra_text: String::new(),
+        });
+        let values = test_to_network!(option);
+        assert_eq!(values.0, &[0x00, 0x0f, 0x00, 0x02, 0x01, 0xf4]);
+    }
+
+    #[test]
+    fn nsid_keepalive() {
+        // NSID and keepalive in a response
+        const PACKET: &str = r#"
+0000   00 03 00 06 67 70 64 6e 73 31 00 0b 00 02 01 2c
+        "#;
+
+        let mut opt = OPT::default();
+        let b = get_sample_slice(PACKET);
+        opt.from_network_bytes(&mut std::io::Cursor::new(b.as_slice()))
+            .unwrap();
+        assert_eq!(opt.options[0], EdnsOption::Nsid(b"gpdns1".to_vec()));
+        assert_eq!(opt.options[1], EdnsOption::TcpKeepalive(Some(300)));
+
+        // to
+        let values = test_to_network!(opt);
+        assert_eq!(values.0, b);
+
+        // both are empty in queries
+        let opt = OPT {
+            options: vec![EdnsOption::Nsid(Vec::new()), EdnsOption::TcpKeepalive(None)],
+            ..OPT::default()
+        };
+        let values = test_to_network!(opt);
+        assert_eq!(values.0, &[0x00, 0x03, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x00]);
+
+        // keepalive is 2 bytes long
+        let b = [0x00, 0x0b, 0x00, 0x01, 0x01];
+        let mut opt = OPT::default();
+        assert!(opt
+            .from_network_bytes(&mut std::io::Cursor::new(b.as_slice()))
+            .is_err());
+    }
+
+    #[test]
+    fn padding() {
+        use crate::rfc1035::{DNSMessage, DNSQuestion};
+
+        let mut query = DNSMessage::default();
+        query.push_question(DNSQuestion::new("www.example.com", QType::A, None).unwrap());
+        let mut opt = OPT::default();
+        opt.push_option(EdnsOption::Padding(Padding::block(
+            QUERY_PADDING_BLOCK_LENGTH,
+        )));
+        query.push_additional(DNSResourceRecord::from(opt));
+
+        // the padding is computed when writing, whatever the position of the message
+        let mut buffer = vec![0xFF; 2];
+        let length = query.to_network_bytes(&mut buffer).unwrap();
+        assert_eq!(length, 128);
+        assert_eq!(buffer.len(), 130);
+
+        // padding octets are zeros and read back as a fixed length
+        let mut message = DNSMessage::default();
+        message
+            .from_network_bytes(&mut std::io::Cursor::new(&buffer[2..]))
+            .unwrap();
+        let padding = message.opt_mut().unwrap().padding_mut().unwrap();
+        assert_eq!(padding.block_length, None);
+        assert_eq!(padding.length, 128 - 48);
+        assert!(buffer[50..].iter().all(|b| *b == 0));
+
+        // responses use a larger block
+        message
+            .opt_mut()
+            .unwrap()
+            .padding_mut()
+            .unwrap()
+            .block_length = Some(RESPONSE_PADDING_BLOCK_LENGTH);
+        let values = test_to_network!(message);
+        assert_eq!(values.1, 468);
+    }
+
+    #[test]
+    fn opt_truncated_option() {
+        // OPTION-LENGTH goes beyond RDATA
+        const PACKET: &str = r#"
+0000   00 00 29 10 00 00 00 00 00 00 06 00 0a 00 08 24
+0010   a1
+        "#;
+
+        let mut rr = DNSResourceRecord::default();
+        let bytes = get_sample_slice(PACKET);
+        let mut buffer = std::io::Cursor::new(bytes.as_slice());
+        assert!(rr.from_network_bytes(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn oversized_option() {
+        // OPTION-DATA over 65535 bytes can't be written
+        for option in [
+            EdnsOption::Nsid(vec![0; 70000]),
+            EdnsOption::Unknown {
+                option_code: 65001,
+                bytes: vec![0; 70000],
+            },
+        ] {
+            let mut buffer: Vec<u8> = Vec::new();
+            let err = option.to_network_bytes(&mut buffer).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+
+        // the largest one can
+        let option = EdnsOption::Nsid(vec![0; u16::MAX as usize]);
+        let mut buffer: Vec<u8> = Vec::new();
+        assert_eq!(option.to_network_bytes(&mut buffer).unwrap(), 4 + 65535);
+        assert_eq!(&buffer[2..4], &[0xFF, 0xFF]);
+    }
+}
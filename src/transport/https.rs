@@ -0,0 +1,106 @@
+//! DNS over HTTPS: https://datatracker.ietf.org/doc/html/rfc8484
+//!
+//! The DNS message is either the body of a POST request, or the base64url encoded `dns` parameter
+//! of a GET request. The response body is the DNS message.
+use std::io::Read;
+use std::time::Duration;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use log::debug;
+use ureq::Agent;
+
+use crate::error::{DNSError, DNSResult};
+use crate::transport::Transport;
+
+// Media type of both the request and the response
+pub const DNS_MESSAGE: &str = "application/dns-message";
+
+// How the query is sent to the server
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum HttpMethod {
+    #[default]
+    Post,
+    Get,
+}
+
+// Each query is a request, the response being kept until received
+#[derive(Debug)]
+pub struct HttpsTransport {
+    agent: Agent,
+    url: String,
+    method: HttpMethod,
+    timeout: Duration,
+    response: Option<Vec<u8>>,
+}
+
+impl HttpsTransport {
+    // The agent keeps the connections to the server, and holds the TLS configuration
+    pub fn new(agent: Agent, url: &str, method: HttpMethod, timeout: Duration) -> Self {
+        Self {
+            agent,
+            url: url.to_string(),
+            method,
+            timeout,
+            response: None,
+        }
+    }
+}
+
+impl Transport for HttpsTransport {
+    fn send(&mut self, message: &[u8]) -> DNSResult<usize> {
+        let response = match self.method {
+            HttpMethod::Post => self
+                .agent
+                .post(&self.url)
+                .timeout(self.timeout)
+                .set("Accept", DNS_MESSAGE)
+                .set("Content-Type", DNS_MESSAGE)
+                .send_bytes(message)?,
+            HttpMethod::Get => self
+                .agent
+                .get(&self.url)
+                .timeout(self.timeout)
+                .set("Accept", DNS_MESSAGE)
+                .query("dns", &URL_SAFE_NO_PAD.encode(message))
+                .call()?,
+        };
+        debug!(
+            "HTTP response: {} {}",
+            response.status(),
+            response.content_type()
+        );
+
+        if response.content_type() != DNS_MESSAGE {
+            return Err(DNSError::new(&format!(
+                "unexpected content type '{}' in HTTP response",
+                response.content_type()
+            )));
+        }
+
+        // a DNS message is at most 65535 bytes long: read one more byte to catch larger bodies
+        let mut body = Vec::new();
+        response
+            .into_reader()
+            .take(u16::MAX as u64 + 1)
+            .read_to_end(&mut body)?;
+        if body.len() > u16::MAX as usize {
+            return Err(DNSError::new("DoH response larger than 65535 bytes"));
+        }
+        self.response = Some(body);
+
+        Ok(message.len())
+    }
+
+    // Only one response is sent back per request: waiting for another one is pointless, so
+    // this isn't a timeout and the exchange fails at once
+    fn recv(&mut self) -> DNSResult<Vec<u8>> {
+        self.response
+            .take()
+            .ok_or_else(|| DNSError::new("no further response on this HTTP exchange"))
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> DNSResult<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! Transports used to exchange DNS messages with a name server
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::error::{DNSError, DNSResult};
+
+pub mod https;
+pub mod quic;
+pub mod tcp;
+pub mod tls;
+pub mod udp;
+
+// Default DNS port for UDP and TCP
+pub const DNS_PORT: u16 = 53;
+
+// Default port for DNS over TLS (RFC7858)
+pub const DNS_OVER_TLS_PORT: u16 = 853;
+
+// Default UDP port for DNS over QUIC (RFC9250)
+pub const DNS_OVER_QUIC_PORT: u16 = 853;
+
+// Each transport sends and receives whole DNS messages, the framing being its own business
+pub trait Transport {
+    // send a DNS message
+    fn send(&mut self, message: &[u8]) -> DNSResult<usize>;
+
+    // receive a DNS message
+    fn recv(&mut self) -> DNSResult<Vec<u8>>;
+
+    // maximum time to wait for the next message
+    fn set_timeout(&mut self, timeout: Duration) -> DNSResult<()>;
+}
+
+/// Split a name server into its host and its port, if any. IPv6 addresses need to be enclosed
+/// in brackets when a port is given.
+///
+/// ```
+/// use dnslib::transport::split_host_port;
+///
+/// assert_eq!(split_host_port("1.1.1.1").unwrap(), ("1.1.1.1", None));
+/// assert_eq!(split_host_port("1.1.1.1:853").unwrap(), ("1.1.1.1", Some(853)));
+/// assert_eq!(split_host_port("::1").unwrap(), ("::1", None));
+/// assert_eq!(split_host_port("[::1]").unwrap(), ("::1", None));
+/// assert_eq!(split_host_port("[::1]:853").unwrap(), ("::1", Some(853)));
+/// assert_eq!(split_host_port("dns.google:853").unwrap(), ("dns.google", Some(853)));
+///
+/// assert!(split_host_port("[::1").is_err());
+/// assert!(split_host_port("[::1]853").is_err());
+/// assert!(split_host_port("1.1.1.1:99999").is_err());
+/// ```
+pub fn split_host_port(server: &str) -> DNSResult<(&str, Option<u16>)> {
+    let port = |p: &str| {
+        p.parse::<u16>()
+            .map_err(|e| DNSError::new(&format!("invalid port in '{}': {}", server, e)))
+    };
+
+    // only an IPv6 address: ::1
+    if server.parse::<Ipv6Addr>().is_ok() {
+        return Ok((server, None));
+    }
+
+    // IPv6 address between brackets, optionally followed by a port: [::1] or [::1]:53
+    if let Some(rest) = server.strip_prefix('[') {
+        let (host, rest) = rest
+            .split_once(']')
+            .ok_or_else(|| DNSError::new(&format!("invalid address '{}'", server)))?;
+        return match rest {
+            "" => Ok((host, None)),
+            _ => match rest.strip_prefix(':') {
+                Some(p) => Ok((host, Some(port(p)?))),
+                None => Err(DNSError::new(&format!("invalid address '{}'", server))),
+            },
+        };
+    }
+
+    // IPv4 address or host name, optionally followed by a port
+    match server.rsplit_once(':') {
+        Some((host, p)) => Ok((host, Some(port(p)?))),
+        None => Ok((server, None)),
+    }
+}
+
+/// Get the socket address of a name server given as an IP address or a host name, optionally
+/// followed by a port. IPv6 addresses need to be enclosed in brackets when a port is given.
+///
+/// ```
+/// use std::net::SocketAddr;
+/// use dnslib::transport::to_socket_addr;
+///
+/// let addr: SocketAddr = "1.1.1.1:53".parse().unwrap();
+/// assert_eq!(to_socket_addr("1.1.1.1", 53).unwrap(), addr);
+///
+/// let addr: SocketAddr = "1.1.1.1:5353".parse().unwrap();
+/// assert_eq!(to_socket_addr("1.1.1.1:5353", 53).unwrap(), addr);
+///
+/// let addr: SocketAddr = "[2606:4700:4700::1111]:53".parse().unwrap();
+/// assert_eq!(to_socket_addr("2606:4700:4700::1111", 53).unwrap(), addr);
+/// assert_eq!(to_socket_addr("[2606:4700:4700::1111]", 53).unwrap(), addr);
+///
+/// let addr: SocketAddr = "[::1]:5353".parse().unwrap();
+/// assert_eq!(to_socket_addr("[::1]:5353", 53).unwrap(), addr);
+///
+/// assert_eq!(to_socket_addr("localhost:5353", 53).unwrap().port(), 5353);
+///
+/// // link-local address with its scope
+/// let addr: SocketAddr = "[fe80::1%2]:53".parse().unwrap();
+/// assert_eq!(to_socket_addr("[fe80::1%2]", 53).unwrap(), addr);
+/// let addr: SocketAddr = "[fe80::1%2]:5353".parse().unwrap();
+/// assert_eq!(to_socket_addr("[fe80::1%2]:5353", 53).unwrap(), addr);
+///
+/// assert!(to_socket_addr("[::1", 53).is_err());
+/// assert!(to_socket_addr("1.1.1.1:99999", 53).is_err());
+/// ```
+pub fn to_socket_addr(server: &str, default_port: u16) -> DNSResult<SocketAddr> {
+    let (host, port) = split_host_port(server)?;
+    let port = port.unwrap_or(default_port);
+
+    // IPv6 address with a scope: fe80::1%2
+    if let Some((ip, scope)) = host.split_once('%') {
+        if let (Ok(ip), Ok(scope_id)) = (ip.parse::<Ipv6Addr>(), scope.parse::<u32>()) {
+            return Ok(SocketAddr::V6(SocketAddrV6::new(ip, port, 0, scope_id)));
+        }
+    }
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port));
+    }
+
+    (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| DNSError::new(&format!("unable to resolve '{}'", server)))
+}
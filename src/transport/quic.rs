@@ -0,0 +1,158 @@
+//! DNS over QUIC: https://datatracker.ietf.org/doc/html/rfc9250
+//!
+//! Each query is sent on its own bidirectional stream, with the same 2-byte length prefix
+//! as over TCP. The server sends the response on the same stream and closes it.
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::debug;
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{Connection, Endpoint, ReadExactError, RecvStream, VarInt};
+use rustls::ClientConfig;
+use tokio::runtime::{Builder, Runtime};
+use tokio::time::timeout as within;
+
+use crate::error::{DNSError, DNSResult};
+use crate::transport::Transport;
+
+// ALPN token identifying DoQ
+pub const DOQ_ALPN: &[u8] = b"doq";
+
+// Error code used when closing the connection without any error
+const DOQ_NO_ERROR: u32 = 0;
+
+#[derive(Debug)]
+pub struct QuicTransport {
+    // quinn is asynchronous, so the transport drives its own runtime
+    runtime: Runtime,
+    endpoint: Endpoint,
+    connection: Connection,
+
+    // stream of the last query, on which the response is received
+    stream: Option<RecvStream>,
+    timeout: Duration,
+}
+
+impl QuicTransport {
+    // Connect and complete the handshake, so that certificate errors are reported here.
+    // The TLS configuration is the same as for DoT, with the DoQ ALPN token.
+    pub fn connect(
+        server: SocketAddr,
+        server_name: &str,
+        config: Arc<ClientConfig>,
+        timeout: Duration,
+    ) -> DNSResult<Self> {
+        let mut config = (*config).clone();
+        config.alpn_protocols = vec![DOQ_ALPN.to_vec()];
+        let config = QuicClientConfig::try_from(Arc::new(config))
+            .map_err(|e| DNSError::new(&format!("TLS configuration unusable with QUIC: {}", e)))?;
+
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let (endpoint, connection) = runtime.block_on(async {
+            let local: SocketAddr = if server.is_ipv4() {
+                "0.0.0.0:0".parse().unwrap()
+            } else {
+                "[::]:0".parse().unwrap()
+            };
+            let endpoint = Endpoint::client(local)?;
+
+            let connecting = endpoint
+                .connect_with(
+                    quinn::ClientConfig::new(Arc::new(config)),
+                    server,
+                    server_name,
+                )
+                .map_err(|e| DNSError::new(&format!("QUIC connection error: {}", e)))?;
+            let connection = within(timeout, connecting)
+                .await
+                .map_err(|_| timed_out("QUIC handshake"))??;
+
+            Ok::<_, DNSError>((endpoint, connection))
+        })?;
+        debug!("QUIC connection to {}", connection.remote_address());
+
+        Ok(Self {
+            runtime,
+            endpoint,
+            connection,
+            stream: None,
+            timeout,
+        })
+    }
+}
+
+impl Transport for QuicTransport {
+    // A new stream per query, closed once the query is sent
+    fn send(&mut self, message: &[u8]) -> DNSResult<usize> {
+        let length =
+            u16::try_from(message.len()).map_err(|_| DNSError::new("message too long for QUIC"))?;
+        let mut framed = length.to_be_bytes().to_vec();
+        framed.extend_from_slice(message);
+
+        let stream = self.runtime.block_on(async {
+            let (mut send, recv) = within(self.timeout, self.connection.open_bi())
+                .await
+                .map_err(|_| timed_out("opening a QUIC stream"))??;
+            send.write_all(&framed).await.map_err(io::Error::from)?;
+            send.finish()
+                .map_err(|e| DNSError::new(&format!("QUIC stream error: {}", e)))?;
+            Ok::<_, DNSError>(recv)
+        })?;
+        self.stream = Some(stream);
+
+        Ok(framed.len())
+    }
+
+    // Only one response is sent back per stream: waiting for another one is pointless, so
+    // this isn't a timeout and the exchange fails at once
+    fn recv(&mut self) -> DNSResult<Vec<u8>> {
+        let mut stream = self
+            .stream
+            .take()
+            .ok_or_else(|| DNSError::new("no further response on this QUIC stream"))?;
+
+        self.runtime.block_on(async {
+            within(self.timeout, async {
+                let mut length = [0u8; 2];
+                stream.read_exact(&mut length).await.map_err(read_error)?;
+
+                let mut message = vec![0u8; u16::from_be_bytes(length) as usize];
+                stream.read_exact(&mut message).await.map_err(read_error)?;
+                Ok(message)
+            })
+            .await
+            .map_err(|_| timed_out("QUIC response"))?
+        })
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> DNSResult<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+// Let the server know we're done
+impl Drop for QuicTransport {
+    fn drop(&mut self) {
+        self.connection.close(VarInt::from_u32(DOQ_NO_ERROR), b"");
+        self.runtime
+            .block_on(async { within(self.timeout, self.endpoint.wait_idle()).await })
+            .ok();
+    }
+}
+
+fn timed_out(what: &str) -> DNSError {
+    DNSError::Io(io::Error::new(io::ErrorKind::TimedOut, what.to_string()))
+}
+
+fn read_error(e: ReadExactError) -> DNSError {
+    match e {
+        ReadExactError::FinishedEarly(_) => DNSError::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "QUIC stream finished early",
+        )),
+        ReadExactError::ReadError(e) => DNSError::Io(io::Error::from(e)),
+    }
+}
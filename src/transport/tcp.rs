@@ -0,0 +1,94 @@
+//! DNS over TCP: https://datatracker.ietf.org/doc/html/rfc7766
+//!
+//! Each message is prefixed with a two byte length field, in network order.
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use log::debug;
+
+use crate::error::DNSResult;
+use crate::transport::Transport;
+
+#[derive(Debug)]
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    // Connect to the server. The timeout is used for connecting, sending and receiving
+    pub fn connect(server: SocketAddr, timeout: Duration) -> DNSResult<Self> {
+        let stream = TcpStream::connect_timeout(&server, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        debug!("stream: {:?}", &stream);
+
+        Ok(Self { stream })
+    }
+}
+
+// Write a length prefixed message. Length prefix and message are sent in a single write
+// as recommended by RFC7766. The length must fit in the prefix.
+pub(crate) fn write_framed<W: Write>(stream: &mut W, message: &[u8]) -> DNSResult<usize> {
+    let length = u16::try_from(message.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too long for TCP"))?;
+
+    let mut buffer = Vec::with_capacity(message.len() + 2);
+    buffer.extend_from_slice(&length.to_be_bytes());
+    buffer.extend_from_slice(message);
+
+    stream.write_all(&buffer)?;
+    Ok(message.len())
+}
+
+// Read a length prefixed message
+pub(crate) fn read_framed<R: Read>(stream: &mut R) -> DNSResult<Vec<u8>> {
+    let mut length = [0u8; 2];
+    stream.read_exact(&mut length)?;
+
+    let mut buffer = vec![0u8; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, message: &[u8]) -> DNSResult<usize> {
+        write_framed(&mut self.stream, message)
+    }
+
+    fn recv(&mut self) -> DNSResult<Vec<u8>> {
+        read_framed(&mut self.stream)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> DNSResult<()> {
+        Ok(self.stream.set_read_timeout(Some(timeout))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framing() {
+        let mut buffer: Vec<u8> = Vec::new();
+        assert_eq!(write_framed(&mut buffer, &[0x12, 0x34, 0x56]).unwrap(), 3);
+        assert_eq!(&buffer, &[0x00, 0x03, 0x12, 0x34, 0x56]);
+
+        let mut cursor = std::io::Cursor::new(buffer);
+        assert_eq!(read_framed(&mut cursor).unwrap(), &[0x12, 0x34, 0x56]);
+
+        // truncated message
+        let mut cursor = std::io::Cursor::new(vec![0x00, 0x03, 0x12]);
+        assert!(read_framed(&mut cursor).is_err());
+
+        // the length doesn't fit in the prefix: nothing is written
+        let mut buffer: Vec<u8> = Vec::new();
+        let message = vec![0u8; u16::MAX as usize + 1];
+        assert!(matches!(
+            write_framed(&mut buffer, &message),
+            Err(crate::error::DNSError::Io(e)) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+        assert!(buffer.is_empty());
+    }
+}
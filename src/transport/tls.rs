@@ -0,0 +1,227 @@
+//! DNS over TLS: https://datatracker.ietf.org/doc/html/rfc7858
+//!
+//! Messages are length prefixed as over TCP, the TCP connection being wrapped into a TLS session.
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::debug;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{ring::default_provider, CryptoProvider};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, StreamOwned};
+use rustls_pki_types::{pem::PemObject, CertificateDer, ServerName, UnixTime};
+
+use crate::error::{DNSError, DNSResult};
+use crate::transport::tcp::{read_framed, write_framed};
+use crate::transport::Transport;
+
+// How the server certificate is authenticated
+#[derive(Debug, Default, Clone)]
+pub struct TlsOptions {
+    // name sent in the SNI and checked against the certificate. Defaults to the server host
+    pub server_name: Option<String>,
+
+    // PEM file of the CA certificates to trust, instead of the built-in Mozilla ones
+    pub ca_file: Option<PathBuf>,
+
+    // base64 SHA-256 digests of the server SubjectPublicKeyInfo (RFC7858 section 4.2). The
+    // certificate must match one of them, and is then only checked against the CAs if
+    // a CA file is given.
+    pub spki_pins: Vec<String>,
+}
+
+impl TlsOptions {
+    // Build the TLS configuration shared by all connections to the server
+    pub fn client_config(&self) -> DNSResult<Arc<ClientConfig>> {
+        let provider = Arc::new(default_provider());
+
+        // trusted CAs
+        let mut roots = RootCertStore::empty();
+        match &self.ca_file {
+            Some(path) => {
+                let certs = CertificateDer::pem_file_iter(path)
+                    .map_err(|e| DNSError::new(&format!("{}: {}", path.display(), e)))?;
+                for cert in certs {
+                    let cert =
+                        cert.map_err(|e| DNSError::new(&format!("{}: {}", path.display(), e)))?;
+                    roots.add(cert)?;
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+
+        let config = if self.spki_pins.is_empty() {
+            builder.with_root_certificates(roots).with_no_client_auth()
+        } else {
+            let pins = self
+                .spki_pins
+                .iter()
+                .map(|pin| STANDARD.decode(pin))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| DNSError::new(&format!("invalid SPKI pin: {}", e)))?;
+
+            let webpki = match &self.ca_file {
+                Some(_) => Some(
+                    WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                        .build()
+                        .map_err(|e| DNSError::new(&e.to_string()))?,
+                ),
+                None => None,
+            };
+
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+                    pins,
+                    webpki,
+                    provider,
+                }))
+                .with_no_client_auth()
+        };
+
+        Ok(Arc::new(config))
+    }
+}
+
+/// Base64 SHA-256 digest of the certificate SubjectPublicKeyInfo, as used for pinning
+///
+/// ```
+/// use rustls_pki_types::CertificateDer;
+/// use dnslib::transport::tls::spki_pin;
+///
+/// assert!(spki_pin(&CertificateDer::from(vec![0u8; 10])).is_err());
+/// ```
+pub fn spki_pin(cert: &CertificateDer<'_>) -> DNSResult<String> {
+    let ee = webpki::EndEntityCert::try_from(cert)
+        .map_err(|e| DNSError::new(&format!("invalid certificate: {}", e)))?;
+    let digest = ring::digest::digest(&ring::digest::SHA256, &ee.subject_public_key_info());
+    Ok(STANDARD.encode(digest.as_ref()))
+}
+
+// Check the server certificate against the SPKI pins, and optionally against the CAs
+#[derive(Debug)]
+struct PinnedVerifier {
+    pins: Vec<Vec<u8>>,
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+        }
+
+        let pin = spki_pin(end_entity).map_err(|e| rustls::Error::General(format!("{:?}", e)))?;
+        let digest = STANDARD.decode(&pin).unwrap_or_default();
+        if self.pins.contains(&digest) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            debug!("SPKI pin {} doesn't match", pin);
+            Err(rustls::Error::General(format!(
+                "server SPKI {} doesn't match any pin",
+                pin
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[derive(Debug)]
+pub struct TlsTransport {
+    stream: StreamOwned<ClientConnection, TcpStream>,
+}
+
+impl TlsTransport {
+    // Connect and complete the handshake, so that certificate errors are reported here. The
+    // timeout is used for connecting, sending and receiving
+    pub fn connect(
+        server: SocketAddr,
+        server_name: ServerName<'static>,
+        config: Arc<ClientConfig>,
+        timeout: Duration,
+    ) -> DNSResult<Self> {
+        let sock = TcpStream::connect_timeout(&server, timeout)?;
+        sock.set_read_timeout(Some(timeout))?;
+        sock.set_write_timeout(Some(timeout))?;
+
+        let conn = ClientConnection::new(config, server_name)?;
+        let mut stream = StreamOwned::new(conn, sock);
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        debug!(
+            "TLS session: {:?} {:?}",
+            stream.conn.protocol_version(),
+            stream.conn.negotiated_cipher_suite()
+        );
+
+        Ok(Self { stream })
+    }
+}
+
+impl Transport for TlsTransport {
+    fn send(&mut self, message: &[u8]) -> DNSResult<usize> {
+        write_framed(&mut self.stream, message)
+    }
+
+    fn recv(&mut self) -> DNSResult<Vec<u8>> {
+        read_framed(&mut self.stream)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> DNSResult<()> {
+        Ok(self.stream.sock.set_read_timeout(Some(timeout))?)
+    }
+}
@@ -0,0 +1,57 @@
+//! DNS over UDP: one message per datagram
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use log::debug;
+
+use crate::error::DNSResult;
+use crate::transport::Transport;
+
+// EDNS allows UDP messages up to 64KB
+const MAX_UDP_MESSAGE_SIZE: usize = 65535;
+
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    server: SocketAddr,
+}
+
+impl UdpTransport {
+    // Bind to an ephemeral local port of the same address family than the server. Receiving
+    // fails if nothing is received before the timeout.
+    //
+    // The socket is connected to the server, so datagrams coming from any other address
+    // are dropped.
+    pub fn new(server: SocketAddr, timeout: Duration) -> DNSResult<Self> {
+        let socket = if server.is_ipv4() {
+            UdpSocket::bind("0.0.0.0:0")?
+        } else {
+            UdpSocket::bind("[::]:0")?
+        };
+        socket.connect(server)?;
+        socket.set_read_timeout(Some(timeout))?;
+        debug!("socket: {:?}", &socket);
+
+        Ok(Self { socket, server })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, message: &[u8]) -> DNSResult<usize> {
+        debug!("destination: {}", self.server);
+        Ok(self.socket.send(message)?)
+    }
+
+    fn recv(&mut self) -> DNSResult<Vec<u8>> {
+        let mut buffer = vec![0u8; MAX_UDP_MESSAGE_SIZE];
+        let received = self.socket.recv(&mut buffer)?;
+        debug!("received {} bytes from {}", received, self.server);
+
+        buffer.truncate(received);
+        Ok(buffer)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> DNSResult<()> {
+        Ok(self.socket.set_read_timeout(Some(timeout))?)
+    }
+}
@@ -65,16 +65,14 @@ pub fn to_domain(domain: &str) -> Vec<u8> {
 }
 
 // Debug utility
-pub fn pretty_cursor<'a>(buffer: &Cursor<&'a [u8]>) {
+pub fn pretty_cursor(buffer: &Cursor<&[u8]>) {
     let reference = buffer.get_ref();
 
     eprintln!("position={}", buffer.position());
 
-    let mut i = 0usize;
     eprint!("index:");
-    for _ in *reference {
+    for i in 0..reference.len() {
         eprint!("{:02} ", i);
-        i += 1;
     }
     eprintln!();
 